@@ -0,0 +1,130 @@
+use crate::store;
+use anyhow::{anyhow, Result};
+use chrono::Local;
+use log::debug;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Number of previous stores kept unless the user asks for a different number.
+pub const DEFAULT_BACKUPS: usize = 3;
+
+/// A previous copy of the store, kept next to it.
+#[derive(Debug)]
+pub struct Backup {
+    /// Position in the rotation, where 1 is the most recent
+    pub number: usize,
+    /// Where the backup is on disk
+    pub path: PathBuf,
+    /// When the backed-up store was last written
+    pub modified: SystemTime,
+}
+
+/// Return the path of the nth backup of the store, like `store.bin.bak.1`.
+pub fn backup_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak.{}", number));
+    path.with_file_name(name)
+}
+
+/// Copy the current store to the first backup slot, shifting older backups up.
+///
+/// Only `keep` backups are kept; the oldest one is dropped. Nothing happens if
+/// the store doesn't exist yet or `keep` is 0.
+pub fn rotate_backups(path: &Path, keep: usize) -> Result<()> {
+    if keep == 0 || !path.exists() {
+        return Ok(());
+    }
+    debug!("Rotating store backups");
+    for number in (1..keep).rev() {
+        let older = backup_path(path, number);
+        if older.exists() {
+            fs::rename(&older, backup_path(path, number + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// List the store's backups, most recent first.
+pub fn list_backups(path: &Path) -> Result<Vec<Backup>> {
+    let mut backups = Vec::new();
+    let mut number = 1;
+    loop {
+        let backup = backup_path(path, number);
+        if !backup.exists() {
+            break;
+        }
+        backups.push(Backup {
+            number,
+            modified: fs::metadata(&backup)?.modified()?,
+            path: backup,
+        });
+        number += 1;
+    }
+    Ok(backups)
+}
+
+/// Swap a backup with the current store, so that restoring it again undoes the restore.
+pub fn restore_backup(path: &Path, number: usize) -> Result<()> {
+    store::ensure_writable()?;
+    let backup = backup_path(path, number);
+    if !backup.exists() {
+        return Err(anyhow!("There is no backup number {}", number));
+    }
+    debug!("Restoring {}", backup.display());
+    if path.exists() {
+        let mut swap_name = backup.file_name().unwrap_or_default().to_os_string();
+        swap_name.push(".swap");
+        let swap = backup.with_file_name(swap_name);
+        fs::rename(path, &swap)?;
+        fs::rename(&backup, path)?;
+        fs::rename(&swap, &backup)?;
+    } else {
+        fs::rename(&backup, path)?;
+    }
+    Ok(())
+}
+
+/// Return the directory that dated backups go in by default, `~/.ppa_backups`.
+pub fn default_archive_dir() -> Result<PathBuf> {
+    Ok(home::home_dir()
+        .ok_or_else(|| anyhow!("Could not find user's home directory"))?
+        .join(".ppa_backups"))
+}
+
+/// Copy the encrypted store into the directory under a dated name like
+/// `ppa_2024-05-01T14-30-00.bin`, then delete all but the newest `keep` copies
+/// (always at least the new one).
+///
+/// Returns the path of the new copy.
+pub fn archive_store(path: &Path, dir: &Path, keep: usize) -> Result<PathBuf> {
+    if !path.exists() {
+        return Err(anyhow!("File does not exist: initialize with `ppa init`"));
+    }
+    fs::create_dir_all(dir)?;
+    let name = format!("ppa_{}.bin", Local::now().format("%Y-%m-%dT%H-%M-%S"));
+    let archive = dir.join(name);
+    debug!("Copying store to {}", archive.display());
+    fs::copy(path, &archive)?;
+
+    let mut archives: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|dir_entry| dir_entry.ok().map(|d| d.path()))
+        .filter(|p| {
+            p.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with("ppa_") && name.ends_with(".bin")
+            })
+        })
+        .collect();
+    // The timestamp format sorts oldest first
+    archives.sort();
+    let excess = archives.len().saturating_sub(keep.max(1));
+    for old in &archives[..excess] {
+        debug!("Removing old backup {}", old.display());
+        fs::remove_file(old)?;
+    }
+    Ok(archive)
+}
@@ -0,0 +1,68 @@
+use crate::{matching_indices, Entry};
+use chrono::Utc;
+use std::fmt;
+
+/// Something wrong with a store's entries, as found by `check_entries`.
+#[derive(Debug)]
+pub enum Problem {
+    /// The named entry has no password
+    EmptyPassword(String),
+    /// Several entries share the name, ignoring case
+    DuplicateName(String, usize),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::EmptyPassword(name) => write!(f, "Entry '{}' has an empty password", name),
+            Problem::DuplicateName(name, count) => {
+                write!(f, "{} entries are named '{}'", count, name)
+            }
+        }
+    }
+}
+
+/// Look for entries with empty passwords and names shared by several entries.
+///
+/// Each shared name is reported once, spelled as its first entry spells it.
+pub fn check_entries(entries: &[Entry]) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = entries
+        .iter()
+        .filter(|entry| entry.password.is_empty())
+        .map(|entry| Problem::EmptyPassword(entry.name.clone()))
+        .collect();
+    let mut seen: Vec<String> = Vec::new();
+    for entry in entries {
+        let name = entry.name.to_lowercase();
+        if seen.contains(&name) {
+            continue;
+        }
+        let count = matching_indices(entries, &name).len();
+        if count > 1 {
+            problems.push(Problem::DuplicateName(entry.name.clone(), count));
+        }
+        seen.push(name);
+    }
+    problems
+}
+
+/// Return the entries that haven't been changed in at least `days` days, with
+/// their age in days, oldest first.
+///
+/// Entries of unknown age, from before timestamps were recorded, count as old
+/// and come first.
+pub fn stale_entries(entries: &[Entry], days: i64) -> Vec<(&Entry, Option<i64>)> {
+    let now = Utc::now();
+    let mut stale: Vec<(&Entry, Option<i64>)> = entries
+        .iter()
+        .filter(|entry| entry.unchanged_for(days))
+        .map(|entry| {
+            let age = entry
+                .last_changed()
+                .map(|changed| (now - changed).num_days());
+            (entry, age)
+        })
+        .collect();
+    stale.sort_by_key(|(_, age)| age.map(std::cmp::Reverse));
+    stale
+}
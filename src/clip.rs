@@ -0,0 +1,196 @@
+use clipboard::{ClipboardContext, ClipboardProvider};
+use log::{info, warn};
+use std::{
+    env,
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+use zeroize::Zeroizing;
+
+/// Longest OSC 52 sequence payload, in base64 bytes, that terminals reliably accept.
+const OSC52_MAX_LENGTH: usize = 100_000;
+
+/// Path of the terminal OSC 52 sequences are written to.
+#[cfg(unix)]
+const TERMINAL_PATH: &str = "/dev/tty";
+#[cfg(not(unix))]
+const TERMINAL_PATH: &str = "CONOUT$";
+
+/// Where copied values go.
+pub(crate) enum Clipboard {
+    /// The system clipboard, through the `clipboard` crate
+    System(ClipboardContext),
+    /// The Wayland clipboard, through `wl-copy` and `wl-paste` from wl-clipboard
+    Wayland,
+    /// The clipboard of the terminal ppa runs in, through OSC 52 escape sequences
+    Osc52(File),
+}
+
+impl Clipboard {
+    /// Connect to the terminal's clipboard if `osc52` is set, to the Wayland
+    /// clipboard in a Wayland session, or to the system's otherwise.
+    ///
+    /// Errors name the clipboard that was tried.
+    pub(crate) fn new(osc52: bool) -> Result<Self, Box<dyn Error>> {
+        if osc52 {
+            return Self::osc52().map_err(|e| format!("OSC 52: {}", e).into());
+        }
+        if env::var_os("WAYLAND_DISPLAY").is_some() {
+            return Self::wayland().map_err(|e| format!("Wayland: {}", e).into());
+        }
+        ClipboardProvider::new()
+            .map(Self::System)
+            .map_err(|e| format!("X11: {}", e).into())
+    }
+
+    fn osc52() -> Result<Self, Box<dyn Error>> {
+        if env::var("TERM").map_or(true, |term| term.is_empty() || term == "dumb") {
+            return Err("the terminal doesn't look like it supports OSC 52".into());
+        }
+        let terminal = OpenOptions::new()
+            .write(true)
+            .open(TERMINAL_PATH)
+            .map_err(|e| format!("could not open the terminal: {}", e))?;
+        Ok(Self::Osc52(terminal))
+    }
+
+    fn wayland() -> Result<Self, Box<dyn Error>> {
+        match Command::new("wl-copy")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+        {
+            Ok(_) => Ok(Self::Wayland),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Err("wl-copy wasn't found; install wl-clipboard".into())
+            }
+            Err(e) => Err(format!("could not run wl-copy: {}", e).into()),
+        }
+    }
+
+    /// Replace the clipboard's contents.
+    ///
+    /// On Wayland, `wl-copy` keeps serving the value after ppa exits.
+    pub(crate) fn set_contents(&mut self, value: String) -> Result<(), Box<dyn Error>> {
+        match self {
+            Self::System(clipboard) => clipboard.set_contents(value),
+            Self::Wayland => wl_copy(&value),
+            Self::Osc52(terminal) => {
+                let encoded = base64::encode(&value);
+                if encoded.len() > OSC52_MAX_LENGTH {
+                    return Err("the value is too long to copy through the terminal".into());
+                }
+                terminal.write_all(osc52_sequence(&encoded).as_bytes())?;
+                terminal.flush()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Read the clipboard's contents, or `None` if this clipboard can't be read back.
+    pub(crate) fn get_contents(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        match self {
+            Self::System(clipboard) => clipboard.get_contents().map(Some),
+            Self::Wayland => wl_paste().map(Some),
+            Self::Osc52(_) => Ok(None),
+        }
+    }
+}
+
+/// Clears the clipboard when dropped if it still holds the copied value, so
+/// it's wiped on a normal exit, a panic, or Ctrl-C.
+pub(crate) struct ClipboardGuard {
+    clipboard: Clipboard,
+    value: Zeroizing<String>,
+}
+
+impl ClipboardGuard {
+    /// Guard a clipboard that the value has been copied into.
+    pub(crate) fn new(clipboard: Clipboard, value: String) -> Self {
+        Self {
+            clipboard,
+            value: Zeroizing::new(value),
+        }
+    }
+}
+
+impl Drop for ClipboardGuard {
+    fn drop(&mut self) {
+        match self.clipboard.get_contents() {
+            Ok(current)
+                if current
+                    .as_deref()
+                    .is_none_or(|current| current == self.value.as_str()) =>
+            {
+                match self.clipboard.set_contents(String::new()) {
+                    Ok(()) => info!("Clipboard cleared"),
+                    Err(e) => warn!("Could not clear your clipboard: {}", e),
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Could not read your clipboard to clear it: {}", e),
+        }
+    }
+}
+
+/// Put the value in the Wayland clipboard, or clear it if the value is empty.
+///
+/// The value goes to `wl-copy` on stdin rather than the command line, where
+/// other users could see it.
+fn wl_copy(value: &str) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new("wl-copy");
+    if value.is_empty() {
+        command.arg("--clear");
+    }
+    // wl-copy leaves a process behind serving the value, which mustn't hold our output open
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run wl-copy: {}", e))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(value.as_bytes())?;
+    drop(stdin);
+    if !child.wait()?.success() {
+        return Err("wl-copy failed".into());
+    }
+    Ok(())
+}
+
+/// Read the Wayland clipboard with `wl-paste`, which is empty if nothing was copied.
+fn wl_paste() -> Result<String, Box<dyn Error>> {
+    let output = Command::new("wl-paste")
+        .args(["--no-newline", "--type", "text"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| format!("could not run wl-paste: {}", e))?;
+    // wl-paste fails when the clipboard is empty
+    if !output.status.success() {
+        return Ok(String::new());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Build the OSC 52 sequence setting the clipboard to the base64 payload,
+/// wrapped so tmux passes it through to the outer terminal.
+fn osc52_sequence(encoded: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+    if env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// Whether OSC 52 should be used without being asked for: over SSH, with no
+/// X11 or Wayland display for the system clipboard to reach.
+pub(crate) fn osc52_detected() -> bool {
+    env::var_os("SSH_TTY").is_some()
+        && env::var_os("DISPLAY").is_none()
+        && env::var_os("WAYLAND_DISPLAY").is_none()
+}
@@ -0,0 +1,872 @@
+use crate::{
+    clip::{Clipboard, ClipboardGuard},
+    copy::{
+        clear_clipboard_after, clear_clipboard_detached, clipboard_context, copy_to_clipboard,
+        output_generated_password, set_clipboard, wait_until_exit,
+    },
+    lookup::{find_entry_index, fuzzy_entry_index, select_entry_index, Ambiguous},
+    output::{
+        print_entries, print_entries_json, print_entry_ages, print_entry_details, print_store_diff,
+        write_output, PasswordDisplay,
+    },
+    prompt::{
+        prompt_confirm, prompt_entry_password, prompt_input, prompt_new_password,
+        prompt_new_store_password, prompt_password, wait_for_enter,
+    },
+    session,
+    unlock::{open_other_store, store_password, supplied_password},
+    AddOptions, CopyOptions, CopyWhat, GenerateOptions, ImportSource, PasswordOptions,
+    SearchOptions,
+};
+use chrono::{DateTime, Local};
+use log::{debug, error, info, warn};
+use ppa::{Cipher, CsvColumns, Entry, ExportFormat, Kdf, PasswordRules};
+use prettytable::{format, row, Table};
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+use zeroize::Zeroizing;
+
+/// The unlocked store, and the settings that commands working on it share.
+pub(crate) struct Context {
+    /// Where the store is
+    pub(crate) store_path: PathBuf,
+    /// Password the store was unlocked with
+    pub(crate) encryption_password: Zeroizing<String>,
+    /// Number of previous stores to keep as backups
+    pub(crate) backups: usize,
+    /// Number of earlier passwords to keep for each entry
+    pub(crate) password_history: usize,
+    /// Default seconds before clearing copied passwords and comments
+    pub(crate) clipboard_timeout: u64,
+    /// Whether to copy through the terminal with OSC 52
+    pub(crate) osc52: bool,
+}
+
+impl Context {
+    /// Write the entries to the store, exiting if that fails.
+    fn save(&self, entries: &[Entry]) {
+        if let Err(e) = ppa::write_store(
+            &self.store_path,
+            entries,
+            &self.encryption_password,
+            self.backups,
+        ) {
+            error!("Could not save store: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Write the lock file.
+pub(crate) fn lock() {
+    match ppa::lock() {
+        Ok(true) => info!("Locked; run `ppa unlock` to use ppa again"),
+        Ok(false) => info!("Already locked"),
+        Err(e) => {
+            error!("Could not write the lock file: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Remove the lock file, once the store has been unlocked.
+pub(crate) fn unlock() {
+    match ppa::unlock() {
+        Ok(true) => info!("Unlocked"),
+        Ok(false) => info!("Not locked"),
+        Err(e) => {
+            error!("Could not remove the lock file: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Create a new store, along with the profiles directory if it's for a profile.
+pub(crate) fn init(
+    store_path: &Path,
+    for_profile: bool,
+    options: &PasswordOptions,
+    kdf: Kdf,
+    cipher: Cipher,
+) {
+    if ppa::store_exists(store_path) {
+        info!("Store already exists at {}", store_path.display());
+        return;
+    }
+    if for_profile {
+        if let Err(e) = ppa::create_profiles_dir() {
+            error!("Could not create the profiles directory: {}", e);
+            process::exit(1);
+        }
+    }
+    let encryption_password = store_password(true, options);
+    match ppa::create_store(store_path, &encryption_password, kdf, cipher) {
+        Ok(()) => info!("Store created"),
+        Err(e) => {
+            error!("Could not create store: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Copy the store into a dated backup in the directory, or `~/.ppa_backups`.
+pub(crate) fn backup(store_path: &Path, dir: Option<PathBuf>, keep: usize) {
+    let dir = match dir.map_or_else(ppa::default_archive_dir, Ok) {
+        Ok(d) => d,
+        Err(e) => {
+            error!("Could not determine backup directory: {}", e);
+            process::exit(1);
+        }
+    };
+    match ppa::archive_store(store_path, &dir, keep) {
+        Ok(path) => info!("Store backed up to {}", path.display()),
+        Err(e) => {
+            error!("Could not back up store: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Print how the entries in two stores differ.
+pub(crate) fn diff(store_a: &Path, store_b: &Path, diff_passwords: bool) {
+    let a = open_other_store(store_a);
+    let b = open_other_store(store_b);
+    print_store_diff(&ppa::diff_stores(&a, &b, diff_passwords));
+}
+
+/// Generate a password, printing it or copying it to the clipboard.
+pub(crate) fn generate(options: &GenerateOptions, print: bool, osc52: bool) {
+    let password = generate_password(options);
+    output_generated_password(password, print, osc52);
+}
+
+/// Add an entry, prompting for its password unless one is generated.
+pub(crate) fn add(context: &Context, entries: &mut Vec<Entry>, options: AddOptions) {
+    debug!("Adding new entry");
+    let totp_secret = options.totp.as_deref().map(parse_totp_secret);
+    if !options.force
+        && entries
+            .iter()
+            .any(|entry| entry.name_matches(&options.name))
+    {
+        error!(
+            "An entry named '{}' already exists; pass --force to add it anyway",
+            options.name
+        );
+        process::exit(1);
+    }
+    let password = match options.generate {
+        Some(length) => {
+            let mut generate_options = GenerateOptions::default();
+            if let Some(length) = length {
+                generate_options.length = length;
+            }
+            let password = generate_password(&generate_options);
+            copy_to_clipboard(password.clone(), context.osc52);
+            info!("Generated a password and copied it to your clipboard");
+            password
+        }
+        None => prompt_entry_password(options.min_strength, &[&options.name, &options.username]),
+    };
+    let mut entry = Entry::new(options.name, options.username, password);
+    entry.url = options.url;
+    entry.comments = options.comments.unwrap_or_default();
+    entry.tags = options.tags;
+    entry.totp_secret = totp_secret;
+    entries.push(entry);
+    context.save(entries);
+    info!("Entry added");
+}
+
+/// Print the entries matching the search, as a table or JSON.
+pub(crate) fn search(entries: &[Entry], options: SearchOptions) {
+    // JSON output goes on to print an empty list, the same as when nothing matches
+    if entries.is_empty() && !options.json {
+        info!("Store is empty");
+        return;
+    }
+    let mut matching = ppa::search_entries(
+        entries
+            .iter()
+            .filter(|entry| options.tag.as_ref().is_none_or(|tag| entry.has_tag(tag)))
+            .filter(|entry| {
+                options
+                    .older_than
+                    .is_none_or(|days| entry.unchanged_for(days))
+            }),
+        options.term.as_deref(),
+        &options.fields,
+    );
+    if let Some(field) = options.sort {
+        ppa::sort_entries(&mut matching, field, options.reverse);
+    }
+    debug!("Found {} matching entries", matching.len());
+    if options.json {
+        if options.include_passwords {
+            warn!("Including passwords in the output");
+        }
+        if let Err(e) = print_entries_json(&matching, options.include_passwords) {
+            error!("Could not write entries: {}", e);
+            process::exit(1);
+        }
+    } else if matching.is_empty() {
+        warn!("No matching entries");
+    } else {
+        print_entries(&matching, options.show_dates);
+    }
+}
+
+/// Copy part of an entry to the clipboard, or write it out instead.
+pub(crate) fn copy(context: &Context, entries: &mut Vec<Entry>, options: CopyOptions) {
+    let index = if options.exact {
+        select_entry_index(entries, &options.name)
+    } else {
+        fuzzy_entry_index(entries, &options.name)
+    };
+    let entry = match index {
+        Ok(Some(index)) => entries.swap_remove(index),
+        Ok(None) => {
+            warn!("Could not find matching entry");
+            return;
+        }
+        Err(Ambiguous) => {
+            error!(
+                "Several entries match and there's no terminal to pick one in; use the exact name"
+            );
+            process::exit(1);
+        }
+    };
+    let what = options.what;
+    let (copy_value, copy_message) = match what {
+        CopyWhat::Username => (entry.username.clone(), "username"),
+        CopyWhat::Password | CopyWhat::Both => (entry.password.clone(), "password"),
+        CopyWhat::Comments => (entry.comments.clone(), "comments"),
+    };
+    if copy_value.is_empty() {
+        warn!("'{}' has no {}; nothing copied", entry.name, copy_message);
+        return;
+    }
+    let output = match options.output {
+        None if options.stdout => Some(PathBuf::from("-")),
+        None if options.allow_stdout_fallback => Clipboard::new(context.osc52).err().map(|e| {
+            warn!(
+                "Could not set up clipboard context: {}; printing the {} instead",
+                e, copy_message
+            );
+            PathBuf::from("-")
+        }),
+        output => output,
+    };
+    if let Some(path) = output {
+        let (content, written) = match what {
+            CopyWhat::Both => (
+                Zeroizing::new(format!("{}\n{}\n", entry.username, copy_value)),
+                "username and password",
+            ),
+            _ => (Zeroizing::new(format!("{}\n", copy_value)), copy_message),
+        };
+        if let Err(e) = write_output(&path, &content) {
+            error!("Could not write to {}: {}", path.display(), e);
+            process::exit(1);
+        }
+        if path != Path::new("-") {
+            info!(
+                "Wrote the {} for '{}' to {}",
+                written,
+                entry.name,
+                path.display()
+            );
+        }
+        return;
+    }
+    let mut clipboard = if let CopyWhat::Both = what {
+        let clipboard = copy_to_clipboard(entry.username.clone(), context.osc52);
+        info!("Copied the username to your clipboard");
+        wait_for_enter("Press enter to copy the password");
+        clipboard
+    } else {
+        clipboard_context(context.osc52)
+    };
+    let default_timeout = match what {
+        CopyWhat::Username => 0,
+        CopyWhat::Password | CopyWhat::Both | CopyWhat::Comments => context.clipboard_timeout,
+    };
+    let clipboard_timeout = options.clipboard_timeout.unwrap_or(default_timeout);
+    if options.clipboard_clear_on_exit {
+        set_clipboard(&mut clipboard, copy_value.clone());
+        info!(
+            "Copied the {} for '{}' to your clipboard; it will clear when ppa exits",
+            copy_message, entry.name
+        );
+        let guard = ClipboardGuard::new(clipboard, copy_value);
+        let interrupted = wait_until_exit(clipboard_timeout);
+        drop(guard);
+        if interrupted {
+            process::exit(130);
+        }
+        return;
+    }
+    if clipboard_timeout > 0
+        && clear_clipboard_detached(&copy_value, clipboard_timeout, context.osc52)
+    {
+        info!(
+            "Copied the {} for '{}' to your clipboard; it will clear in {}s",
+            copy_message, entry.name, clipboard_timeout
+        );
+        return;
+    }
+    set_clipboard(&mut clipboard, copy_value.clone());
+    info!(
+        "Copied the {} for '{}' to your clipboard",
+        copy_message, entry.name
+    );
+    if clipboard_timeout > 0 {
+        clear_clipboard_after(&mut clipboard, &copy_value, clipboard_timeout);
+    }
+}
+
+/// Print an entry's current TOTP code, copying it as well if asked to.
+pub(crate) fn totp(context: &Context, entries: &[Entry], name: &str, copy: bool) {
+    let entry = match find_entry_index(entries, name) {
+        Some(index) => &entries[index],
+        None => {
+            warn!("Could not find matching entry");
+            process::exit(1);
+        }
+    };
+    let secret = match &entry.totp_secret {
+        Some(s) => s,
+        None => {
+            error!("Entry '{}' has no TOTP secret", entry.name);
+            process::exit(1);
+        }
+    };
+    let (code, remaining) = match ppa::totp_code(secret) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not generate a TOTP code: {}", e);
+            process::exit(1);
+        }
+    };
+    println!("{} ({} seconds remaining)", code, remaining);
+    if copy {
+        copy_to_clipboard(code, context.osc52);
+        info!("Copied the code to your clipboard");
+    }
+}
+
+/// Print every field of the entries with the name, masking the password unless
+/// `reveal` is set.
+pub(crate) fn show(entries: &[Entry], name: &str, reveal: bool, no_password: bool) {
+    let matching: Vec<&Entry> = ppa::matching_indices(entries, name)
+        .into_iter()
+        .map(|index| &entries[index])
+        .collect();
+    if matching.is_empty() {
+        warn!("Could not find matching entry");
+        process::exit(1);
+    }
+    let password = if no_password {
+        PasswordDisplay::Hidden
+    } else if reveal {
+        PasswordDisplay::Revealed
+    } else {
+        PasswordDisplay::Masked
+    };
+    print_entry_details(&matching, password);
+}
+
+/// Print an entry's earlier passwords, most recent first.
+pub(crate) fn history(entries: &[Entry], name: &str) {
+    let entry = match find_entry_index(entries, name) {
+        Some(index) => &entries[index],
+        None => {
+            error!("Could not find an entry named '{}'", name);
+            process::exit(1);
+        }
+    };
+    if entry.previous_passwords.is_empty() {
+        info!("No earlier passwords for {}", entry.name);
+        return;
+    }
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["Number", "Password"]);
+    for (index, password) in entry.previous_passwords.iter().enumerate() {
+        table.add_row(row![index + 1, password]);
+    }
+    table.printstd();
+}
+
+/// Generate a password for a new entry, then print or copy it.
+pub(crate) fn generate_entry(
+    context: &Context,
+    entries: &mut Vec<Entry>,
+    options: &GenerateOptions,
+    print: bool,
+    name: String,
+    username: Option<String>,
+) {
+    debug!("Adding new entry with a generated password");
+    let password = generate_password(options);
+    entries.push(Entry::new(
+        name,
+        username.unwrap_or_default(),
+        password.clone(),
+    ));
+    context.save(entries);
+    info!("Entry added");
+    output_generated_password(password, print, context.osc52);
+}
+
+/// Change the given fields of an entry, prompting for the password if asked to.
+pub(crate) fn edit(
+    context: &Context,
+    entries: &mut [Entry],
+    name: &str,
+    username: Option<String>,
+    comments: Option<String>,
+    password: bool,
+) {
+    if username.is_none() && comments.is_none() && !password {
+        warn!("Nothing to edit; pass --username, --comments, or --password");
+        process::exit(1);
+    }
+    let entry = match find_entry_index(entries, name) {
+        Some(index) => &mut entries[index],
+        None => {
+            warn!("Could not find matching entry");
+            process::exit(1);
+        }
+    };
+    debug!("Editing entry");
+    if let Some(username) = username {
+        entry.username = username;
+    }
+    if let Some(comments) = comments {
+        entry.comments = comments;
+    }
+    if password {
+        entry.set_password(
+            prompt_password("Entry password", true),
+            context.password_history,
+        );
+    }
+    entry.touch();
+    context.save(entries);
+    info!("Entry updated");
+}
+
+/// Prompt for each of an entry's fields in turn, saving it if any changed.
+pub(crate) fn update(context: &Context, entries: &mut [Entry], name: &str, totp: Option<&str>) {
+    let entry = match find_entry_index(entries, name) {
+        Some(index) => &mut entries[index],
+        None => {
+            error!("Could not find an entry named '{}'", name);
+            process::exit(1);
+        }
+    };
+    if !atty::is(atty::Stream::Stdin) {
+        error!("`ppa update` prompts for each field and needs a terminal; use `ppa edit` instead");
+        process::exit(1);
+    }
+    debug!("Updating entry");
+    let before = entry.clone();
+    entry.username = prompt_input("Username", &entry.username);
+    entry.comments = prompt_input("Comments", &entry.comments);
+    if let Some(password) = prompt_new_password() {
+        entry.set_password(password, context.password_history);
+    }
+    match totp {
+        Some("") => entry.totp_secret = None,
+        Some(secret) => entry.totp_secret = Some(parse_totp_secret(secret)),
+        None => {}
+    }
+    if *entry == before {
+        info!("Nothing changed");
+        return;
+    }
+    entry.touch();
+    context.save(entries);
+    info!("Entry updated");
+}
+
+/// Rename an entry, refusing a name that's taken unless `force` is set.
+pub(crate) fn rename(
+    context: &Context,
+    entries: &mut [Entry],
+    old: &str,
+    new: String,
+    force: bool,
+) {
+    let index = match find_entry_index(entries, old) {
+        Some(index) => index,
+        None => {
+            warn!("Could not find matching entry");
+            return;
+        }
+    };
+    let taken = entries
+        .iter()
+        .enumerate()
+        .any(|(i, entry)| i != index && entry.name_matches(&new));
+    if taken {
+        if !force {
+            error!(
+                "An entry named '{}' already exists; pass --force to rename anyway",
+                new
+            );
+            process::exit(1);
+        }
+        warn!("An entry named '{}' already exists", new);
+    }
+    debug!("Renaming entry");
+    entries[index].name = new;
+    entries[index].touch();
+    context.save(entries);
+    info!("Entry renamed");
+}
+
+/// Write every entry out in plaintext, to the file or stdout.
+pub(crate) fn export(entries: &[Entry], format: &ExportFormat, output: Option<&Path>) {
+    warn!(
+        "The export contains every password in PLAINTEXT; store it safely and delete it when done"
+    );
+    let result = match output {
+        Some(path) => ppa::create_private_file(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| ppa::export_entries(entries, format, file)),
+        None => ppa::export_entries(entries, format, io::stdout()),
+    };
+    if let Err(e) = result {
+        error!("Could not export store: {}", e);
+        process::exit(1);
+    }
+    info!("Exported {} entries", entries.len());
+}
+
+/// Import entries from another password manager, merging them into the store
+/// or replacing it.
+pub(crate) fn import(context: &Context, entries: &mut Vec<Entry>, source: ImportSource) {
+    let (imported, options) = match source {
+        ImportSource::Csv {
+            path,
+            columns,
+            options,
+        } => (ppa::import_csv(&path, &columns.into()), options),
+        ImportSource::OnePassword { path, options } => {
+            (ppa::import_csv(&path, &CsvColumns::one_password()), options)
+        }
+        ImportSource::Chrome { path, options } => (ppa::import_chrome(&path), options),
+        ImportSource::Json { path, options } => (ppa::import_json(&path), options),
+        ImportSource::Bitwarden { path, options } => (ppa::import_bitwarden(&path), options),
+        ImportSource::Pass { dir, options } => {
+            let dir = dir
+                .or_else(|| env::var_os("PASSWORD_STORE_DIR").map(PathBuf::from))
+                .or_else(|| home::home_dir().map(|home| home.join(".password-store")));
+            match dir {
+                Some(dir) => (ppa::import_pass(&dir), options),
+                None => {
+                    error!("Could not find the pass store directory");
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    let imported = match imported {
+        Ok(i) => i,
+        Err(e) => {
+            error!("Could not read import file: {}", e);
+            process::exit(1);
+        }
+    };
+    for (location, reason) in &imported.skipped {
+        warn!("Skipped {}: {}", location, reason);
+    }
+    let skipped = imported.skipped.len();
+    if options.dry_run {
+        if imported.entries.is_empty() {
+            warn!("Nothing to import");
+        } else {
+            print_entries(&imported.entries.iter().collect::<Vec<_>>(), false);
+        }
+        info!(
+            "Would import {} entries, skipping {}",
+            imported.entries.len(),
+            skipped
+        );
+        return;
+    }
+    let summary = if options.replace {
+        if !prompt_confirm(&format!(
+            "Replace all {} existing entries with {} imported ones?",
+            entries.len(),
+            imported.entries.len()
+        )) {
+            info!("Import cancelled");
+            return;
+        }
+        ppa::replace_entries(entries, imported.entries)
+    } else {
+        ppa::merge_entries(
+            entries,
+            imported.entries,
+            options.overwrite,
+            context.password_history,
+        )
+    };
+    for name in &summary.duplicates {
+        warn!("Skipped {}: an entry with that name already exists", name);
+    }
+    if !summary.duplicates.is_empty() {
+        info!("Pass --overwrite to replace existing entries");
+    }
+    if summary.added + summary.replaced > 0 {
+        context.save(entries);
+    }
+    info!(
+        "Imported {} entries ({} added, {} replaced), skipped {}",
+        summary.added + summary.replaced,
+        summary.added,
+        summary.replaced,
+        skipped + summary.duplicates.len()
+    );
+}
+
+/// List the entries not changed in `days` days, exiting with an error if there are any.
+pub(crate) fn age(entries: &[Entry], days: i64) {
+    let old = ppa::stale_entries(entries, days);
+    if old.is_empty() {
+        info!("No entries are older than {} days", days);
+        return;
+    }
+    print_entry_ages(&old);
+    error!(
+        "{} entries have not been changed in {} days",
+        old.len(),
+        days
+    );
+    process::exit(1);
+}
+
+/// Describe the store on disk and report problems with its entries.
+pub(crate) fn check(context: &Context, entries: &[Entry]) {
+    match fs::metadata(&context.store_path) {
+        Ok(metadata) => {
+            println!("Store is {} bytes on disk", metadata.len());
+            if let Ok(modified) = metadata.modified() {
+                let modified: DateTime<Local> = modified.into();
+                println!("Last written {}", modified.format("%Y-%m-%d %H:%M:%S"));
+            }
+        }
+        Err(e) => warn!("Could not read the store's metadata: {}", e),
+    }
+    println!("Decrypted {} entries", entries.len());
+    let problems = ppa::check_entries(entries);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        error!("Found {} problem(s)", problems.len());
+        process::exit(1);
+    }
+    info!("No problems found");
+}
+
+/// Print how many entries the store has, in total and for each tag, and its size.
+pub(crate) fn count(context: &Context, entries: &[Entry]) {
+    println!("{} entries", entries.len());
+    let mut tag_counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in entries.iter().flat_map(|entry| &entry.tags) {
+        *tag_counts.entry(tag).or_default() += 1;
+    }
+    for (tag, count) in tag_counts {
+        println!("  {}: {}", tag, count);
+    }
+    match fs::metadata(&context.store_path) {
+        Ok(metadata) => println!("Store is {} bytes on disk", metadata.len()),
+        Err(e) => warn!("Could not read the store's size: {}", e),
+    }
+}
+
+/// Re-encrypt the store with a new password.
+pub(crate) fn change_password(context: &Context, entries: &[Entry]) {
+    let new_password = Zeroizing::new(prompt_new_store_password("New store password"));
+    if let Err(e) = ppa::write_store(&context.store_path, entries, &new_password, context.backups) {
+        error!("Could not save store: {}", e);
+        process::exit(1);
+    }
+    info!("Store password changed");
+}
+
+/// Remove an entry, asking which one if several share the name.
+pub(crate) fn remove(context: &Context, entries: &mut Vec<Entry>, name: &str) {
+    // Without a terminal to pick in, refuse to guess which same-named entry to remove
+    let index = if atty::is(atty::Stream::Stdin) {
+        select_entry_index(entries, name).unwrap_or_else(|Ambiguous| process::exit(1))
+    } else {
+        find_entry_index(entries, name)
+    };
+    match index {
+        Some(index) => {
+            entries.remove(index);
+            context.save(entries);
+            info!("Entry removed");
+        }
+        None => warn!("could not find matching entry"),
+    }
+}
+
+/// Start an interactive session on the unlocked store.
+pub(crate) fn session(context: &Context, entries: &mut Vec<Entry>, read_only: bool) {
+    let session = session::Session {
+        store_path: &context.store_path,
+        encryption_password: &context.encryption_password,
+        backups: context.backups,
+        clipboard_timeout: context.clipboard_timeout,
+        osc52: context.osc52,
+        read_only,
+    };
+    session::run(&session, entries);
+}
+
+/// Print the profiles that have a store, starting with the default one if it exists.
+///
+/// With `counts`, each store is unlocked to count its entries.
+pub(crate) fn profiles(default_store: Option<PathBuf>, options: &PasswordOptions, counts: bool) {
+    let mut profiles = match ppa::list_profiles() {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read profiles: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(path) = default_store.filter(|p| ppa::store_exists(p)) {
+        profiles.insert(
+            0,
+            ppa::Profile {
+                name: ppa::DEFAULT_PROFILE.to_owned(),
+                path,
+            },
+        );
+    }
+    if profiles.is_empty() {
+        info!("No profiles found");
+        return;
+    }
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    if counts {
+        table.set_titles(row!["Profile", "Path", "Entries"]);
+    } else {
+        table.set_titles(row!["Profile", "Path"]);
+    }
+    for profile in &profiles {
+        if !counts {
+            table.add_row(row![profile.name, profile.path.display()]);
+            continue;
+        }
+        let password = Zeroizing::new(supplied_password(options).unwrap_or_else(|| {
+            prompt_password(&format!("Store password for {}", profile.name), false)
+        }));
+        let entries = match ppa::read_store(&profile.path, &password) {
+            Ok(entries) => entries.len().to_string(),
+            Err(e) => {
+                warn!("Could not load {}: {}", profile.name, e);
+                "?".to_owned()
+            }
+        };
+        table.add_row(row![profile.name, profile.path.display(), entries]);
+    }
+    table.printstd();
+}
+
+/// Normalize a TOTP secret given on the command line, exiting if it isn't valid base32.
+pub(crate) fn parse_totp_secret(secret: &str) -> String {
+    match ppa::normalize_totp_secret(secret) {
+        Ok(secret) => secret,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Generate a random password, exiting if the options are invalid.
+pub(crate) fn generate_password(options: &GenerateOptions) -> String {
+    let rules = PasswordRules {
+        length: options.length,
+        uppercase: !options.no_uppercase,
+        digits: !options.no_digits,
+        symbols: !options.no_symbols,
+        exclude_ambiguous: options.exclude_ambiguous,
+    };
+    match ppa::generate_password(&rules) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not generate password: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// List the store's backups, or restore one after confirming with the user.
+pub(crate) fn restore_backup(store_path: &Path, number: Option<usize>) {
+    let backups = match ppa::list_backups(store_path) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Could not read backups: {}", e);
+            process::exit(1);
+        }
+    };
+    let number = match number {
+        Some(n) => n,
+        None => {
+            if backups.is_empty() {
+                info!("No backups found");
+                return;
+            }
+            let mut table = Table::new();
+            table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+            table.set_titles(row!["Number", "Written", "Path"]);
+            for backup in &backups {
+                let written: DateTime<Local> = backup.modified.into();
+                table.add_row(row![
+                    backup.number,
+                    written.format("%Y-%m-%d %H:%M:%S"),
+                    backup.path.display()
+                ]);
+            }
+            table.printstd();
+            return;
+        }
+    };
+    let backup = match backups.iter().find(|backup| backup.number == number) {
+        Some(b) => b,
+        None => {
+            error!("There is no backup number {}", number);
+            process::exit(1);
+        }
+    };
+    let written: DateTime<Local> = backup.modified.into();
+    warn!(
+        "This replaces the store with the backup written {}",
+        written.format("%Y-%m-%d %H:%M:%S")
+    );
+    if !prompt_confirm("Restore the backup?") {
+        info!("Restore cancelled");
+        return;
+    }
+    if let Err(e) = ppa::restore_backup(store_path, number) {
+        error!("Could not restore backup: {}", e);
+        process::exit(1);
+    }
+    info!(
+        "Backup restored; the replaced store is now backup {}",
+        number
+    );
+}
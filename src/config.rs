@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{fs, path::PathBuf};
+
+/// Seconds to wait before clearing the clipboard when neither flag nor config set it.
+pub(crate) const DEFAULT_CLIPBOARD_TIMEOUT: u64 = 45;
+
+/// Defaults read from `~/.ppa.toml`, used when the matching flag isn't given.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    /// Path to the store file
+    pub(crate) store_path: Option<PathBuf>,
+    /// Seconds to wait before clearing the clipboard after copying
+    pub(crate) clipboard_timeout: Option<u64>,
+    /// Number of earlier passwords to keep for each entry
+    pub(crate) password_history: Option<usize>,
+}
+
+/// Return the path to the config file in the user's home directory.
+fn path_to_config() -> Option<PathBuf> {
+    home::home_dir().map(|home| home.join(".ppa.toml"))
+}
+
+/// Read the config file, falling back to an empty config if there isn't one.
+pub(crate) fn load() -> Result<Config> {
+    let path = match path_to_config() {
+        Some(p) if p.exists() => p,
+        _ => return Ok(Config::default()),
+    };
+    let content = fs::read_to_string(&path)?;
+    toml::from_str(&content).map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
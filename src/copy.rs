@@ -0,0 +1,170 @@
+use crate::{clip::Clipboard, prompt::wait_for_enter};
+use log::{debug, error, info, warn};
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{self, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+use zeroize::Zeroizing;
+
+/// Line `ppa clear-clip-helper` prints once the clipboard holds the value it was given.
+const CLEAR_CLIP_READY: &str = "ready";
+
+/// Copy a value to the user's clipboard, returning the clipboard context.
+pub(crate) fn copy_to_clipboard(value: String, osc52: bool) -> Clipboard {
+    let mut clipboard = clipboard_context(osc52);
+    set_clipboard(&mut clipboard, value);
+    clipboard
+}
+
+/// Set up access to the clipboard, or the terminal's with `osc52`, exiting if it isn't available.
+pub(crate) fn clipboard_context(osc52: bool) -> Clipboard {
+    match Clipboard::new(osc52) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not set up clipboard context: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Replace the clipboard's contents.
+pub(crate) fn set_clipboard(clipboard: &mut Clipboard, value: String) {
+    if let Err(e) = clipboard.set_contents(value) {
+        error!("Could not copy value to your clipboard: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Wait, then clear the clipboard if it still holds the value that was copied.
+///
+/// A clipboard that can't be read back, like the terminal's, is always
+/// cleared. Pressing Ctrl-C while waiting clears the clipboard right away.
+pub(crate) fn clear_clipboard_after(clipboard: &mut Clipboard, value: &str, seconds: u64) {
+    let (interrupt_tx, interrupt_rx) = mpsc::channel();
+    if let Err(e) = ctrlc::set_handler(move || {
+        let _ = interrupt_tx.send(());
+    }) {
+        warn!("Could not watch for Ctrl-C: {}", e);
+    }
+    info!("Clearing the clipboard in {} seconds", seconds);
+    let interrupted = interrupt_rx
+        .recv_timeout(Duration::from_secs(seconds))
+        .is_ok();
+    match clipboard.get_contents() {
+        Ok(current) if current.as_deref().is_none_or(|current| current == value) => {
+            if let Err(e) = clipboard.set_contents(String::new()) {
+                error!("Could not clear your clipboard: {}", e);
+                process::exit(1);
+            }
+            info!("Clipboard cleared");
+        }
+        Ok(_) => debug!("Clipboard contents changed; leaving them alone"),
+        Err(e) => warn!("Could not read your clipboard to clear it: {}", e),
+    }
+    if interrupted {
+        process::exit(130);
+    }
+}
+
+/// Wait for the timeout, or for enter to be pressed if it's 0, returning
+/// early with true if Ctrl-C is pressed.
+pub(crate) fn wait_until_exit(seconds: u64) -> bool {
+    let (interrupt_tx, interrupt_rx) = mpsc::channel();
+    if let Err(e) = ctrlc::set_handler(move || {
+        let _ = interrupt_tx.send(());
+    }) {
+        warn!("Could not watch for Ctrl-C: {}", e);
+    }
+    if seconds > 0 {
+        info!("Waiting {} seconds; press Ctrl-C to clear it now", seconds);
+        return interrupt_rx
+            .recv_timeout(Duration::from_secs(seconds))
+            .is_ok();
+    }
+    let (enter_tx, enter_rx) = mpsc::channel();
+    thread::spawn(move || {
+        wait_for_enter("Press enter to clear the clipboard and exit");
+        let _ = enter_tx.send(());
+    });
+    loop {
+        if interrupt_rx.try_recv().is_ok() {
+            return true;
+        }
+        if enter_rx.recv_timeout(Duration::from_millis(100)).is_ok() {
+            return false;
+        }
+    }
+}
+
+/// Start a detached `ppa clear-clip-helper` process that puts the value in the
+/// clipboard and clears it after the timeout, so this one can exit right away.
+///
+/// The value is passed on stdin rather than the command line, where other
+/// users could see it. Returns false if the process couldn't take over the clipboard.
+pub(crate) fn clear_clipboard_detached(value: &str, seconds: u64, osc52: bool) -> bool {
+    let exe = match env::current_exe() {
+        Ok(e) => e,
+        Err(e) => {
+            debug!("Could not find the ppa executable: {}", e);
+            return false;
+        }
+    };
+    let mut command = process::Command::new(exe);
+    if osc52 {
+        command.arg("--osc52");
+    }
+    command
+        .arg("clear-clip-helper")
+        .arg(seconds.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // A process group of its own keeps Ctrl-C in the terminal from reaching it
+        command.process_group(0);
+    }
+    let ready = command.spawn().and_then(|mut child| {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(value.as_bytes())?;
+        drop(stdin);
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().expect("stdout is piped")).read_line(&mut line)?;
+        Ok(line.trim_end() == CLEAR_CLIP_READY)
+    });
+    match ready {
+        Ok(ready) => ready,
+        Err(e) => {
+            debug!("Could not start a process to clear the clipboard: {}", e);
+            false
+        }
+    }
+}
+
+/// Run as `ppa clear-clip-helper`: put the value from stdin in the clipboard, tell
+/// the parent it's there, and clear it after the timeout.
+pub(crate) fn hold_clipboard(seconds: u64, osc52: bool) {
+    let mut value = Zeroizing::new(String::new());
+    if let Err(e) = io::stdin().read_to_string(&mut value) {
+        error!("Could not read the value to copy: {}", e);
+        process::exit(1);
+    }
+    let mut clipboard = copy_to_clipboard(value.to_string(), osc52);
+    println!("{}", CLEAR_CLIP_READY);
+    clear_clipboard_after(&mut clipboard, &value, seconds);
+}
+
+/// Hand a generated password to the user, either in their clipboard or on stdout.
+pub(crate) fn output_generated_password(password: String, print: bool, osc52: bool) {
+    if print {
+        println!("{}", password);
+    } else {
+        copy_to_clipboard(password, osc52);
+        info!("Copied the password to your clipboard");
+    }
+}
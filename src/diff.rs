@@ -0,0 +1,58 @@
+use crate::Entry;
+
+/// How the entries in two stores differ, pairing entries up by name.
+#[derive(Debug, Default)]
+pub struct StoreDiff {
+    /// Names of entries only in the first store
+    pub only_a: Vec<String>,
+    /// Names of entries only in the second store
+    pub only_b: Vec<String>,
+    /// Names of entries in both stores, with the fields that differ between them
+    pub changed: Vec<(String, Vec<&'static str>)>,
+}
+
+impl StoreDiff {
+    /// Whether both stores have the same entries with the same fields.
+    pub fn is_empty(&self) -> bool {
+        self.only_a.is_empty() && self.only_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare two stores' entries by username, URL, and comments.
+///
+/// Passwords are only compared if `passwords` is set, and even then only
+/// whether they differ is reported.
+pub fn diff_stores(a: &[Entry], b: &[Entry], passwords: bool) -> StoreDiff {
+    let mut diff = StoreDiff::default();
+    for entry in a {
+        let other = match b.iter().find(|other| other.name_matches(&entry.name)) {
+            Some(o) => o,
+            None => {
+                diff.only_a.push(entry.name.clone());
+                continue;
+            }
+        };
+        let mut fields = Vec::new();
+        if entry.username != other.username {
+            fields.push("username");
+        }
+        if entry.url != other.url {
+            fields.push("url");
+        }
+        if entry.comments != other.comments {
+            fields.push("comments");
+        }
+        if passwords && entry.password != other.password {
+            fields.push("password");
+        }
+        if !fields.is_empty() {
+            diff.changed.push((entry.name.clone(), fields));
+        }
+    }
+    diff.only_b = b
+        .iter()
+        .filter(|entry| !a.iter().any(|other| other.name_matches(&entry.name)))
+        .map(|entry| entry.name.clone())
+        .collect();
+    diff
+}
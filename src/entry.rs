@@ -0,0 +1,153 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::arg_enum;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Borrow, cmp::Ordering};
+use zeroize::Zeroize;
+
+/// Number of earlier passwords kept for each entry unless the config says otherwise.
+pub const DEFAULT_PASSWORD_HISTORY: usize = 5;
+
+/// A single entry in the store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Name of the site/service
+    pub name: String,
+    /// Login username
+    pub username: String,
+    /// Login password
+    pub password: String,
+    /// Earlier passwords, most recent first
+    #[serde(default)]
+    pub previous_passwords: Vec<String>,
+    /// Address of the site/service
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Any user comments
+    pub comments: String,
+    /// Labels for grouping entries
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Base32 seed for generating TOTP codes
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    /// When the entry was added, if it was added after timestamps were recorded
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the entry was last changed, if it was changed after timestamps were recorded
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Entry {
+    /// Create an entry with no URL, comments, tags, or TOTP secret, added just now.
+    pub fn new(name: String, username: String, password: String) -> Self {
+        let now = Some(Utc::now());
+        Self {
+            name,
+            username,
+            password,
+            previous_passwords: Vec::new(),
+            url: None,
+            comments: String::new(),
+            tags: Vec::new(),
+            totp_secret: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// When the entry was last changed, falling back to when it was added.
+    pub fn last_changed(&self) -> Option<DateTime<Utc>> {
+        self.updated_at.or(self.created_at)
+    }
+
+    /// Whether the entry hasn't been changed in at least `days` days, which is
+    /// assumed if it doesn't say when it was.
+    pub fn unchanged_for(&self, days: i64) -> bool {
+        self.last_changed()
+            .is_none_or(|changed| Utc::now() - changed >= Duration::days(days))
+    }
+
+    /// Record that the entry was just changed.
+    pub fn touch(&mut self) {
+        self.updated_at = Some(Utc::now());
+    }
+
+    /// Change the password, keeping the old one in the history, which is cut
+    /// down to the `history` most recent passwords.
+    pub fn set_password(&mut self, password: String, history: usize) {
+        if password != self.password {
+            let old = std::mem::replace(&mut self.password, password);
+            self.previous_passwords.insert(0, old);
+        }
+        self.previous_passwords.truncate(history);
+    }
+
+    /// Whether this entry's name matches the given name, ignoring case.
+    pub fn name_matches(&self, name: &str) -> bool {
+        self.name.to_lowercase() == name.to_lowercase()
+    }
+
+    /// Whether this entry has the given tag, ignoring case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .iter()
+            .any(|t| t.to_lowercase() == tag.to_lowercase())
+    }
+}
+
+/// Wipe the entry's secrets from memory when it's dropped.
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.previous_passwords.zeroize();
+        self.totp_secret.zeroize();
+    }
+}
+
+/// Return the indices of all entries whose name matches, ignoring case.
+pub fn matching_indices(entries: &[Entry], name: &str) -> Vec<usize> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.name_matches(name))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+arg_enum! {
+    /// Entry fields that entries can be sorted by.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum SortField {
+        Name,
+        Username,
+        Created,
+        Updated,
+    }
+}
+
+/// Sort entries by the field: names and usernames alphabetically, ignoring
+/// case, and timestamps newest first, with entries that have none last.
+///
+/// The sort is stable, so entries that tie keep their order, even when `reverse`
+/// inverts it.
+pub fn sort_entries<E: Borrow<Entry>>(entries: &mut [E], field: SortField, reverse: bool) {
+    let newest_first = |a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>| match (a, b) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    };
+    entries.sort_by(|a, b| {
+        let (a, b) = (a.borrow(), b.borrow());
+        let ordering: Ordering = match field {
+            SortField::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortField::Username => a.username.to_lowercase().cmp(&b.username.to_lowercase()),
+            SortField::Created => newest_first(a.created_at, b.created_at),
+            SortField::Updated => newest_first(a.updated_at, b.updated_at),
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
@@ -0,0 +1,51 @@
+use crate::Entry;
+use anyhow::Result;
+use clap::arg_enum;
+use log::debug;
+use serde::Serialize;
+use std::io::Write;
+
+arg_enum! {
+    /// File formats that entries can be exported as.
+    #[derive(Debug)]
+    pub enum ExportFormat {
+        Csv,
+        Json,
+    }
+}
+
+/// A single CSV row, laid out the way browsers and other managers import them.
+#[derive(Debug, Serialize)]
+struct CsvRow<'a> {
+    name: &'a str,
+    url: &'a str,
+    username: &'a str,
+    password: &'a str,
+    notes: &'a str,
+}
+
+/// Write the entries, unencrypted, in the chosen format.
+pub fn export_entries<W: Write>(entries: &[Entry], format: &ExportFormat, writer: W) -> Result<()> {
+    debug!("Exporting {} entries as {}", entries.len(), format);
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for entry in entries {
+                writer.serialize(CsvRow {
+                    name: &entry.name,
+                    url: entry.url.as_deref().unwrap_or_default(),
+                    username: &entry.username,
+                    password: &entry.password,
+                    notes: &entry.comments,
+                })?;
+            }
+            writer.flush()?;
+        }
+        ExportFormat::Json => {
+            let mut writer = writer;
+            serde_json::to_writer_pretty(&mut writer, entries)?;
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::{anyhow, Result};
+use rand::{seq::SliceRandom, thread_rng};
+
+/// Character classes that a generated password can draw from.
+#[derive(Debug, Clone, Copy)]
+pub struct PasswordRules {
+    /// Number of characters in the password
+    pub length: usize,
+    /// Include uppercase letters
+    pub uppercase: bool,
+    /// Include digits
+    pub digits: bool,
+    /// Include symbols
+    pub symbols: bool,
+    /// Leave out characters that are easily confused with one another
+    pub exclude_ambiguous: bool,
+}
+
+const LOWERCASE_CHARS: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE_CHARS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGIT_CHARS: &str = "0123456789";
+const SYMBOL_CHARS: &str = "!@#$%^&*()-_=+[]{};:,.<>/?~";
+const AMBIGUOUS_CHARS: &str = "il1Lo0O";
+
+/// Generate a random password from the character classes enabled in the rules.
+///
+/// The password is guaranteed to contain at least one character from each
+/// enabled class.
+pub fn generate_password(rules: &PasswordRules) -> Result<String> {
+    let mut classes = vec![LOWERCASE_CHARS];
+    if rules.uppercase {
+        classes.push(UPPERCASE_CHARS);
+    }
+    if rules.digits {
+        classes.push(DIGIT_CHARS);
+    }
+    if rules.symbols {
+        classes.push(SYMBOL_CHARS);
+    }
+    let classes: Vec<Vec<char>> = classes
+        .iter()
+        .map(|class| {
+            class
+                .chars()
+                .filter(|c| !rules.exclude_ambiguous || !AMBIGUOUS_CHARS.contains(*c))
+                .collect()
+        })
+        .collect();
+    if rules.length < classes.len() {
+        return Err(anyhow!(
+            "Password length must be at least {} to include every character class",
+            classes.len()
+        ));
+    }
+
+    let mut rng = thread_rng();
+    let charset: Vec<char> = classes.iter().flatten().cloned().collect();
+    let mut password: Vec<char> = classes
+        .iter()
+        .map(|class| *class.choose(&mut rng).expect("classes are never empty"))
+        .collect();
+    while password.len() < rules.length {
+        password.push(*charset.choose(&mut rng).expect("charset is never empty"));
+    }
+    password.shuffle(&mut rng);
+    Ok(password.into_iter().collect())
+}
@@ -0,0 +1,452 @@
+use crate::Entry;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::debug;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Names of the CSV columns that hold each entry field.
+#[derive(Debug, Clone)]
+pub struct CsvColumns {
+    /// Column holding the entry name
+    pub name: String,
+    /// Column holding the username
+    pub username: String,
+    /// Column holding the password
+    pub password: String,
+    /// Column holding the URL, if there is one
+    pub url: String,
+    /// Column holding the comments, if there is one
+    pub comments: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            name: "name".to_owned(),
+            username: "username".to_owned(),
+            password: "password".to_owned(),
+            url: "url".to_owned(),
+            comments: "comments".to_owned(),
+        }
+    }
+}
+
+impl CsvColumns {
+    /// Columns used by 1Password's CSV export.
+    pub fn one_password() -> Self {
+        Self {
+            name: "title".to_owned(),
+            comments: "notes".to_owned(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Find the index of a column by header name, ignoring case and any byte order mark.
+fn find_column(headers: &csv::StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|header| {
+        header.trim_start_matches('\u{feff}').trim().to_lowercase() == name.to_lowercase()
+    })
+}
+
+/// Entries read from an import source, along with what had to be skipped.
+#[derive(Debug, Default)]
+pub struct Imported {
+    /// Entries that were read successfully
+    pub entries: Vec<Entry>,
+    /// Where each skipped item was (like a line number) and why it was skipped
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Read entries from a CSV file, mapping columns onto entry fields by header name.
+///
+/// The name, username, and password columns are required in the header. Rows
+/// that can't be parsed or are missing one of those values are skipped. If
+/// the comments column is left at its default and isn't present, a `notes`
+/// column is used instead. Quoted multiline comments keep their line breaks,
+/// normalized to `\n`.
+pub fn import_csv(path: &Path, columns: &CsvColumns) -> Result<Imported> {
+    debug!("Importing entries from CSV");
+    read_csv(path, columns, false)
+}
+
+/// Columns used by Chrome and Edge password exports.
+fn chrome_columns() -> CsvColumns {
+    CsvColumns {
+        comments: "note".to_owned(),
+        ..CsvColumns::default()
+    }
+}
+
+/// Get the host out of a URL like `https://user@example.com:8080/login`.
+fn url_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit('@').next().unwrap_or_default();
+    let host = host_port.split(':').next().unwrap_or_default();
+    Some(host).filter(|host| !host.is_empty())
+}
+
+/// Read entries from a Chrome or Edge passwords CSV export.
+///
+/// Rows with an empty name are named after the host of their URL, and names
+/// that appear more than once in the file get the username appended.
+pub fn import_chrome(path: &Path) -> Result<Imported> {
+    debug!("Importing entries from Chrome CSV");
+    let mut imported = read_csv(path, &chrome_columns(), true)?;
+    let names: Vec<String> = imported
+        .entries
+        .iter()
+        .map(|entry| entry.name.clone())
+        .collect();
+    for entry in &mut imported.entries {
+        if names.iter().filter(|name| **name == entry.name).count() > 1 {
+            entry.name = format!("{} ({})", entry.name, entry.username);
+        }
+    }
+    Ok(imported)
+}
+
+/// Read entries from a CSV file, optionally naming unnamed rows after their URL host.
+fn read_csv(path: &Path, columns: &CsvColumns, name_from_url: bool) -> Result<Imported> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let required = |name: &str| {
+        find_column(&headers, name).ok_or_else(|| anyhow!("CSV has no '{}' column", name))
+    };
+    let name_column = required(&columns.name)?;
+    let username_column = required(&columns.username)?;
+    let password_column = required(&columns.password)?;
+    let url_column = find_column(&headers, &columns.url);
+    let comments_column = find_column(&headers, &columns.comments).or_else(|| {
+        if columns.comments == CsvColumns::default().comments {
+            find_column(&headers, "notes")
+        } else {
+            None
+        }
+    });
+
+    let mut imported = Imported::default();
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line());
+                imported
+                    .skipped
+                    .push((format!("line {}", line), e.to_string()));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .unwrap_or_default()
+                .to_owned()
+        };
+        let mut entry = Entry::new(
+            field(Some(name_column)),
+            field(Some(username_column)),
+            field(Some(password_column)),
+        );
+        entry.url = Some(field(url_column)).filter(|url| !url.is_empty());
+        // Exports from Windows, like 1Password's, quote multiline notes with CRLF inside
+        entry.comments = field(comments_column).replace("\r\n", "\n");
+        if name_from_url && entry.name.is_empty() {
+            if let Some(host) = entry.url.as_deref().and_then(url_host) {
+                entry.name = host.to_owned();
+            }
+        }
+        let missing: Vec<&str> = [
+            ("name", &entry.name),
+            ("username", &entry.username),
+            ("password", &entry.password),
+        ]
+        .iter()
+        .filter(|(_, value)| value.is_empty())
+        .map(|(field, _)| *field)
+        .collect();
+        if !missing.is_empty() {
+            imported.skipped.push((
+                format!("line {}", line),
+                format!("missing {}", missing.join(", ")),
+            ));
+            continue;
+        }
+        imported.entries.push(entry);
+    }
+    debug!(
+        "Read {} entries and skipped {} rows",
+        imported.entries.len(),
+        imported.skipped.len()
+    );
+    Ok(imported)
+}
+
+/// Collect the paths of every `.gpg` file under a directory, skipping hidden ones.
+fn find_gpg_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            find_gpg_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "gpg") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Build an entry from a decrypted `pass` file.
+///
+/// The first line is the password. `username:`, `user:`, `login:`, and `url:`
+/// lines fill in those fields, and every other line goes into the comments.
+fn parse_pass_file(name: String, content: &str) -> Entry {
+    let mut lines = content.lines();
+    let password = lines.next().unwrap_or_default().to_owned();
+    let mut username = String::new();
+    let mut url = None;
+    let mut comments = Vec::new();
+    for line in lines {
+        let (key, value) = match line.find(':') {
+            Some(index) => (
+                line[..index].trim().to_lowercase(),
+                line[index + 1..].trim(),
+            ),
+            None => (String::new(), line),
+        };
+        match key.as_str() {
+            "username" | "user" | "login" if username.is_empty() => username = value.to_owned(),
+            "url" if url.is_none() => url = Some(value.to_owned()),
+            _ => comments.push(line),
+        }
+    }
+    let mut entry = Entry::new(name, username, password);
+    entry.url = url;
+    entry.comments = comments.join("\n").trim().to_owned();
+    entry
+}
+
+/// Read entries from a `pass` password store, decrypting each file with `gpg`.
+///
+/// Each entry is named after its path in the store. Files that can't be
+/// decrypted are skipped.
+pub fn import_pass(dir: &Path) -> Result<Imported> {
+    debug!("Importing entries from pass store at {}", dir.display());
+    let mut files = Vec::new();
+    find_gpg_files(dir, &mut files)?;
+    files.sort();
+
+    let mut imported = Imported::default();
+    for file in files {
+        let name = file
+            .strip_prefix(dir)
+            .unwrap_or(&file)
+            .with_extension("")
+            .to_string_lossy()
+            .into_owned();
+        let output = match Command::new("gpg")
+            .args(["--quiet", "--decrypt"])
+            .arg(&file)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) => return Err(anyhow!("Could not run gpg: {}", e)),
+        };
+        if !output.status.success() {
+            let reason = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+            imported.skipped.push((name, reason));
+            continue;
+        }
+        match String::from_utf8(output.stdout) {
+            Ok(content) => imported.entries.push(parse_pass_file(name, &content)),
+            Err(_) => imported
+                .skipped
+                .push((name, "decrypted content is not UTF-8".to_owned())),
+        }
+    }
+    debug!(
+        "Read {} entries and skipped {} files",
+        imported.entries.len(),
+        imported.skipped.len()
+    );
+    Ok(imported)
+}
+
+/// Read entries from a JSON file in the format written by `ppa export`.
+///
+/// Entries missing a name or password are skipped. Usernames may be empty,
+/// since some import sources don't have them.
+pub fn import_json(path: &Path) -> Result<Imported> {
+    debug!("Importing entries from JSON");
+    let entries: Vec<Entry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut imported = Imported::default();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let missing: Vec<&str> = [("name", &entry.name), ("password", &entry.password)]
+            .iter()
+            .filter(|(_, value)| value.is_empty())
+            .map(|(field, _)| *field)
+            .collect();
+        if missing.is_empty() {
+            imported.entries.push(entry);
+        } else {
+            imported.skipped.push((
+                format!("entry {}", index + 1),
+                format!("missing {}", missing.join(", ")),
+            ));
+        }
+    }
+    Ok(imported)
+}
+
+/// The parts of a Bitwarden JSON export that map onto entries.
+#[derive(Debug, Deserialize)]
+struct BitwardenExport {
+    items: Vec<BitwardenItem>,
+}
+
+/// A single item in a Bitwarden export.
+#[derive(Debug, Deserialize)]
+struct BitwardenItem {
+    #[serde(rename = "type")]
+    item_type: u8,
+    name: String,
+    notes: Option<String>,
+    login: Option<BitwardenLogin>,
+}
+
+/// Login details of a Bitwarden item.
+#[derive(Debug, Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    #[serde(default)]
+    uris: Vec<BitwardenUri>,
+}
+
+/// A URI attached to a Bitwarden login.
+#[derive(Debug, Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+/// Bitwarden's item type for logins.
+const BITWARDEN_LOGIN_TYPE: u8 = 1;
+
+/// Read entries from an unencrypted Bitwarden JSON export.
+///
+/// The first URI becomes the entry URL, and any other URIs and the notes go
+/// into the comments. Items that aren't logins, or have no password, are skipped.
+pub fn import_bitwarden(path: &Path) -> Result<Imported> {
+    debug!("Importing entries from Bitwarden export");
+    let export: BitwardenExport = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut imported = Imported::default();
+    for item in export.items {
+        let login = match item.login {
+            Some(login) if item.item_type == BITWARDEN_LOGIN_TYPE => login,
+            _ => {
+                imported
+                    .skipped
+                    .push((item.name, "not a login item".to_owned()));
+                continue;
+            }
+        };
+        let password = match login.password {
+            Some(password) if !password.is_empty() => password,
+            _ => {
+                imported
+                    .skipped
+                    .push((item.name, "missing password".to_owned()));
+                continue;
+            }
+        };
+        let mut uris = login.uris.into_iter().filter_map(|u| u.uri);
+        let url = uris.next();
+        let comments: Vec<String> = uris.chain(item.notes).collect();
+        let mut entry = Entry::new(item.name, login.username.unwrap_or_default(), password);
+        entry.url = url;
+        entry.comments = comments.join("\n");
+        imported.entries.push(entry);
+    }
+    debug!(
+        "Read {} entries and skipped {} items",
+        imported.entries.len(),
+        imported.skipped.len()
+    );
+    Ok(imported)
+}
+
+/// Counts of what happened when merging imported entries into the store.
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    /// Entries that didn't share a name with an existing entry
+    pub added: usize,
+    /// Existing entries that were replaced by an imported one
+    pub replaced: usize,
+    /// Names of imported entries that were dropped because the name was taken
+    pub duplicates: Vec<String>,
+}
+
+/// Replace every entry in the store with the imported ones.
+///
+/// As with `merge_entries`, imported entries without timestamps are stamped
+/// with the current time.
+pub fn replace_entries(entries: &mut Vec<Entry>, mut imported: Vec<Entry>) -> MergeSummary {
+    let now = Some(Utc::now());
+    for entry in &mut imported {
+        entry.created_at = entry.created_at.or(now);
+        entry.updated_at = entry.updated_at.or(now);
+    }
+    let summary = MergeSummary {
+        added: imported.len(),
+        ..MergeSummary::default()
+    };
+    *entries = imported;
+    summary
+}
+
+/// Merge imported entries into the store, replacing same-named entries only if asked to.
+///
+/// Imported entries without timestamps are stamped with the current time. A
+/// replaced entry's password history carries over to the imported one, keeping
+/// up to `history` earlier passwords.
+pub fn merge_entries(
+    entries: &mut Vec<Entry>,
+    imported: Vec<Entry>,
+    overwrite: bool,
+    history: usize,
+) -> MergeSummary {
+    let mut summary = MergeSummary::default();
+    let now = Some(Utc::now());
+    for mut entry in imported {
+        entry.created_at = entry.created_at.or(now);
+        entry.updated_at = entry.updated_at.or(now);
+        match entries.iter().position(|e| e.name_matches(&entry.name)) {
+            Some(index) if overwrite => {
+                let password =
+                    std::mem::replace(&mut entry.password, entries[index].password.clone());
+                entry.previous_passwords = std::mem::take(&mut entries[index].previous_passwords);
+                entry.set_password(password, history);
+                entries[index] = entry;
+                summary.replaced += 1;
+            }
+            Some(_) => summary.duplicates.push(entry.name.clone()),
+            None => {
+                entries.push(entry);
+                summary.added += 1;
+            }
+        }
+    }
+    summary
+}
@@ -0,0 +1,44 @@
+//! Store handling, searching, checks, password generation, and import/export for `ppa`.
+//!
+//! The `ppa` binary adds the command line, prompts, clipboard, and table output
+//! on top of these functions.
+
+mod backup;
+mod check;
+mod diff;
+mod entry;
+mod export;
+mod generate;
+mod import;
+mod lock;
+mod profile;
+mod search;
+mod store;
+mod strength;
+mod totp;
+
+pub use backup::{
+    archive_store, default_archive_dir, list_backups, restore_backup, Backup, DEFAULT_BACKUPS,
+};
+pub use check::{check_entries, stale_entries, Problem};
+pub use diff::{diff_stores, StoreDiff};
+pub use entry::{matching_indices, sort_entries, Entry, SortField, DEFAULT_PASSWORD_HISTORY};
+pub use export::{export_entries, ExportFormat};
+pub use generate::{generate_password, PasswordRules};
+pub use import::{
+    import_bitwarden, import_chrome, import_csv, import_json, import_pass, merge_entries,
+    replace_entries, CsvColumns, Imported, MergeSummary,
+};
+pub use lock::{is_locked, lock, lock_path, unlock};
+pub use profile::{
+    create_profiles_dir, list_profiles, profile_path, profiles_dir, Profile, DEFAULT_PROFILE,
+};
+pub use search::{fuzzy_matching_indices, search_entries, SearchField};
+pub use store::{
+    check_password_length, create_private_file, create_store, default_store_path, is_read_only,
+    legacy_store_path, load_store, loose_permissions, move_store, path_to_store, read_store,
+    restrict_permissions, set_read_only, store_exists, write_store, Cipher, Kdf, ReadOnly,
+    WrongPassword, MIN_PASSWORD_LENGTH,
+};
+pub use strength::{password_strength, strength_label, MIN_RECOMMENDED_STRENGTH};
+pub use totp::{normalize_totp_secret, totp_code};
@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::debug;
+use std::{fs, io, path::PathBuf};
+
+/// Return the path to the lock file in the user's home directory, `~/.ppa.lock`.
+pub fn lock_path() -> Result<PathBuf> {
+    Ok(home::home_dir()
+        .ok_or_else(|| anyhow!("Could not find user's home directory"))?
+        .join(".ppa.lock"))
+}
+
+/// Return whether `ppa lock` has been run without a matching `ppa unlock`.
+pub fn is_locked() -> Result<bool> {
+    Ok(lock_path()?.exists())
+}
+
+/// Write the lock file, recording when it was locked.
+///
+/// Returns false if it was already locked.
+pub fn lock() -> Result<bool> {
+    let path = lock_path()?;
+    if path.exists() {
+        return Ok(false);
+    }
+    debug!("Writing lock file to {}", path.display());
+    fs::write(&path, format!("{}\n", Utc::now().to_rfc3339()))?;
+    Ok(true)
+}
+
+/// Remove the lock file.
+///
+/// Returns false if it wasn't locked.
+pub fn unlock() -> Result<bool> {
+    let path = lock_path()?;
+    debug!("Removing lock file at {}", path.display());
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
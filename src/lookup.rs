@@ -0,0 +1,84 @@
+use dialoguer::{theme::ColorfulTheme, Select};
+use log::{error, info, warn};
+use ppa::Entry;
+use std::process;
+
+/// Find the single entry with the given name, exiting if several share it.
+pub(crate) fn find_entry_index(entries: &[Entry], name: &str) -> Option<usize> {
+    match ppa::matching_indices(entries, name).as_slice() {
+        [] => None,
+        [index] => Some(*index),
+        _ => {
+            error!(
+                "Multiple entries are named '{}'; disambiguate them first",
+                name
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Several entries matched a name, and there was no terminal to pick one in.
+#[derive(Debug)]
+pub(crate) struct Ambiguous;
+
+/// Ask the user to pick one of the entries at the given indices.
+///
+/// Without a terminal to ask in, the entries are listed instead.
+fn choose_entry(entries: &[Entry], indices: &[usize], prompt: &str) -> Result<usize, Ambiguous> {
+    let items: Vec<String> = indices
+        .iter()
+        .map(|&index| format!("{} ({})", entries[index].name, entries[index].username))
+        .collect();
+    if !atty::is(atty::Stream::Stdin) {
+        for item in &items {
+            warn!("Matches {}", item);
+        }
+        return Err(Ambiguous);
+    }
+    let prompt_theme = ColorfulTheme::default();
+    match Select::with_theme(&prompt_theme)
+        .with_prompt(prompt)
+        .items(&items)
+        .default(0)
+        .interact()
+    {
+        Ok(choice) => Ok(indices[choice]),
+        Err(e) => {
+            error!("Could not prompt for an entry: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Find the entry with the given name, asking the user to pick one if several share it.
+pub(crate) fn select_entry_index(
+    entries: &[Entry],
+    name: &str,
+) -> Result<Option<usize>, Ambiguous> {
+    let matching = ppa::matching_indices(entries, name);
+    match matching.as_slice() {
+        [] => Ok(None),
+        [index] => Ok(Some(*index)),
+        _ => choose_entry(entries, &matching, "Several entries match; pick one").map(Some),
+    }
+}
+
+/// Find the entry with the given name, falling back to fuzzy matching if none has it.
+///
+/// A single fuzzy match is used as-is; if several entries match, the user picks
+/// from them, best first.
+pub(crate) fn fuzzy_entry_index(entries: &[Entry], name: &str) -> Result<Option<usize>, Ambiguous> {
+    if let Some(index) = select_entry_index(entries, name)? {
+        return Ok(Some(index));
+    }
+    let matching = ppa::fuzzy_matching_indices(entries, name);
+    match matching.as_slice() {
+        [] => Ok(None),
+        [index] => {
+            info!("Using '{}'", entries[*index].name);
+            Ok(Some(*index))
+        }
+        _ => choose_entry(entries, &matching, "Several entries match; pick one").map(Some),
+    }
+}
@@ -1,13 +1,34 @@
-use clipboard::{ClipboardContext, ClipboardProvider};
-use dialoguer::{theme::ColorfulTheme, Password};
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use clap::{arg_enum, AppSettings, ArgMatches, Shell};
+use copy::hold_clipboard;
 use log::{debug, error, info, warn};
-use prettytable::{cell, format, row, Table};
-use std::process;
+use ppa::{Cipher, CsvColumns, ExportFormat, Kdf, SearchField, SortField};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    process,
+};
 use structopt::StructOpt;
 
-mod util;
-use util::{CopyWhat, Entry};
+mod clip;
+mod commands;
+mod config;
+mod copy;
+mod lookup;
+mod output;
+mod prompt;
+mod session;
+mod unlock;
+
+arg_enum! {
+    /// What the user wants to copy into their clipboard; `Both` copies the username, then the password.
+    #[derive(Debug)]
+    enum CopyWhat {
+        Username,
+        Password,
+        Both,
+        Comments,
+    }
+}
 
 /// Main CLI options;
 #[derive(Debug, StructOpt)]
@@ -19,36 +40,568 @@ struct Options {
     #[structopt(short, long, help = "Enable debug logging")]
     debug: bool,
 
+    #[structopt(
+        short,
+        long,
+        env = "PPA_STORE",
+        parse(from_os_str),
+        help = "Path to the store file [default: store.bin in ppa's data directory]"
+    )]
+    store: Option<PathBuf>,
+
+    #[structopt(
+        short,
+        long,
+        env = "PPA_PROFILE",
+        help = "Named store to use, kept at ~/.ppa/<name>.bin [default: default]"
+    )]
+    profile: Option<String>,
+
+    #[structopt(flatten)]
+    password: PasswordOptions,
+
+    #[structopt(long, help = "Exit on a wrong store password instead of asking again")]
+    no_retry: bool,
+
+    #[structopt(
+        long,
+        help = "Never write to the store, refusing commands that would change it"
+    )]
+    read_only: bool,
+
+    #[structopt(
+        long,
+        help = "Copy to the terminal's clipboard with OSC 52, for use over SSH [default: on over SSH without a display]"
+    )]
+    osc52: bool,
+
+    #[structopt(
+        long,
+        env = "PPA_BACKUPS",
+        default_value = "3",
+        help = "Number of previous stores to keep as backups"
+    )]
+    backups: usize,
+
     #[structopt(subcommand)]
     command: Option<Subcommand>,
 }
 
+/// Where to get the store password from instead of prompting for it.
+#[derive(Debug, StructOpt)]
+struct PasswordOptions {
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Read the store password from this file instead of prompting; anyone who can read the file can open the store"
+    )]
+    password_file: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        conflicts_with = "password-file",
+        help = "Read the store password from the first line of stdin; anything else the command reads from stdin comes after it"
+    )]
+    password_stdin: bool,
+}
+
+impl PasswordOptions {
+    /// Whether the store password will be typed in at a prompt.
+    fn typed(&self) -> bool {
+        self.password_file.is_none()
+            && !self.password_stdin
+            && env::var_os("PPA_PASSWORD").is_none()
+            && atty::is(atty::Stream::Stdin)
+    }
+}
+
+/// Options controlling how passwords are generated.
+#[derive(Debug, StructOpt)]
+struct GenerateOptions {
+    #[structopt(short, long, default_value = "24", help = "Number of characters")]
+    length: usize,
+    #[structopt(long, help = "Exclude symbols")]
+    no_symbols: bool,
+    #[structopt(long, help = "Exclude digits")]
+    no_digits: bool,
+    #[structopt(long, help = "Exclude uppercase letters")]
+    no_uppercase: bool,
+    #[structopt(
+        long,
+        help = "Exclude easily confused characters like 'l', '1', 'O', and '0'"
+    )]
+    exclude_ambiguous: bool,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            length: 24,
+            no_symbols: false,
+            no_digits: false,
+            no_uppercase: false,
+            exclude_ambiguous: false,
+        }
+    }
+}
+
+/// Options shared by every import source.
+#[derive(Debug, StructOpt)]
+struct ImportOptions {
+    #[structopt(long, help = "Replace existing entries that share a name")]
+    overwrite: bool,
+    #[structopt(
+        long,
+        conflicts_with = "overwrite",
+        help = "Replace the whole store with the imported entries instead of merging"
+    )]
+    replace: bool,
+    #[structopt(long, help = "Show what would be imported without saving anything")]
+    dry_run: bool,
+}
+
+/// CSV header names to read each entry field from.
+#[derive(Debug, StructOpt)]
+struct CsvColumnOptions {
+    #[structopt(long, default_value = "name", help = "Column holding the entry name")]
+    name_column: String,
+    #[structopt(long, default_value = "username", help = "Column holding the username")]
+    username_column: String,
+    #[structopt(long, default_value = "password", help = "Column holding the password")]
+    password_column: String,
+    #[structopt(long, default_value = "url", help = "Column holding the URL")]
+    url_column: String,
+    #[structopt(
+        long,
+        default_value = "comments",
+        help = "Column holding the comments; falls back to 'notes'"
+    )]
+    comments_column: String,
+}
+
+impl Subcommand {
+    /// Whether the command can write its own output to stdout, so logs have to go elsewhere.
+    fn writes_to_stdout(&self) -> bool {
+        match self {
+            Subcommand::Export { output, .. } => output.is_none(),
+            Subcommand::Search(options) => options.json,
+            Subcommand::ClearClip { .. } => true,
+            Subcommand::Copy(options) => {
+                options.stdout
+                    || options.allow_stdout_fallback
+                    || options.output.as_deref() == Some(Path::new("-"))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether running the command with its flags can change the store on disk.
+    ///
+    /// The store itself refuses writes under `--read-only`; this lets ppa say
+    /// so before asking for the store password.
+    fn writes_store(&self) -> bool {
+        match self {
+            Subcommand::Generate { name, .. } => name.is_some(),
+            Subcommand::Import { source } => !source.options().dry_run,
+            Subcommand::Init { .. }
+            | Subcommand::RestoreBackup { .. }
+            | Subcommand::Add(_)
+            | Subcommand::Edit { .. }
+            | Subcommand::Update { .. }
+            | Subcommand::Rename { .. }
+            | Subcommand::ChangePassword {}
+            | Subcommand::Remove { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl ImportSource {
+    /// The options shared by every import source.
+    fn options(&self) -> &ImportOptions {
+        match self {
+            ImportSource::Csv { options, .. }
+            | ImportSource::OnePassword { options, .. }
+            | ImportSource::Chrome { options, .. }
+            | ImportSource::Json { options, .. }
+            | ImportSource::Pass { options, .. }
+            | ImportSource::Bitwarden { options, .. } => options,
+        }
+    }
+}
+
+impl From<CsvColumnOptions> for CsvColumns {
+    fn from(options: CsvColumnOptions) -> Self {
+        Self {
+            name: options.name_column,
+            username: options.username_column,
+            password: options.password_column,
+            url: options.url_column,
+            comments: options.comments_column,
+        }
+    }
+}
+
+/// Where `import` reads entries from.
+#[derive(Debug, StructOpt)]
+enum ImportSource {
+    #[structopt(about = "Import from a CSV file with a header row")]
+    Csv {
+        #[structopt(parse(from_os_str), help = "File to read from")]
+        path: PathBuf,
+        #[structopt(flatten)]
+        columns: CsvColumnOptions,
+        #[structopt(flatten)]
+        options: ImportOptions,
+    },
+    #[structopt(name = "1password", about = "Import from a 1Password CSV export")]
+    OnePassword {
+        #[structopt(parse(from_os_str), help = "File to read from")]
+        path: PathBuf,
+        #[structopt(flatten)]
+        options: ImportOptions,
+    },
+    #[structopt(about = "Import from a Chrome or Edge passwords CSV export")]
+    Chrome {
+        #[structopt(parse(from_os_str), help = "File to read from")]
+        path: PathBuf,
+        #[structopt(flatten)]
+        options: ImportOptions,
+    },
+    #[structopt(about = "Import from a JSON file written by `ppa export --format json`")]
+    Json {
+        #[structopt(parse(from_os_str), help = "File to read from")]
+        path: PathBuf,
+        #[structopt(flatten)]
+        options: ImportOptions,
+    },
+    #[structopt(about = "Import from a pass (password-store) directory using gpg")]
+    Pass {
+        #[structopt(
+            parse(from_os_str),
+            help = "Store directory [default: $PASSWORD_STORE_DIR or ~/.password-store]"
+        )]
+        dir: Option<PathBuf>,
+        #[structopt(flatten)]
+        options: ImportOptions,
+    },
+    #[structopt(about = "Import from an unencrypted Bitwarden JSON export")]
+    Bitwarden {
+        #[structopt(parse(from_os_str), help = "File to read from")]
+        path: PathBuf,
+        #[structopt(flatten)]
+        options: ImportOptions,
+    },
+}
+
+/// Options for adding an entry.
+#[derive(Debug, StructOpt)]
+struct AddOptions {
+    #[structopt(short, long, help = "Name of site/service")]
+    name: String,
+    #[structopt(short, long, help = "Username")]
+    username: String,
+    #[structopt(long, help = "Address of site/service")]
+    url: Option<String>,
+    #[structopt(short, long, help = "Comments")]
+    comments: Option<String>,
+    #[structopt(
+        short,
+        long = "tag",
+        number_of_values = 1,
+        help = "Tag to label the entry with; can be given more than once"
+    )]
+    tags: Vec<String>,
+    #[structopt(
+        short,
+        long,
+        value_name = "length",
+        help = "Generate a random password and copy it to your clipboard instead of prompting"
+    )]
+    generate: Option<Option<usize>>,
+    #[structopt(
+        long,
+        value_name = "0-4",
+        possible_values = &["0", "1", "2", "3", "4"],
+        conflicts_with = "generate",
+        help = "Ask for another password if the one given scores below this strength"
+    )]
+    min_strength: Option<u8>,
+    #[structopt(
+        long,
+        alias = "totp-secret",
+        value_name = "secret",
+        help = "Base32 TOTP secret for two-factor codes"
+    )]
+    totp: Option<String>,
+    #[structopt(
+        short,
+        long,
+        help = "Add the entry even if one with the same name exists"
+    )]
+    force: bool,
+}
+
+/// Options for searching the store.
+#[derive(Debug, StructOpt)]
+struct SearchOptions {
+    #[structopt(help = "Term to search for; leave blank to list all")]
+    term: Option<String>,
+    #[structopt(short, long, help = "Only include entries with this tag")]
+    tag: Option<String>,
+    #[structopt(
+        short,
+        long,
+        possible_values = &SearchField::variants(),
+        case_insensitive = true,
+        use_delimiter = true,
+        help = "Fields to search, separated by commas [default: all]"
+    )]
+    fields: Vec<SearchField>,
+    #[structopt(long, possible_values = &SortField::variants(), case_insensitive = true, help = "Sort by a field instead of by how well entries match; dates are newest first")]
+    sort: Option<SortField>,
+    #[structopt(long, requires = "sort", help = "Reverse the sort order")]
+    reverse: bool,
+    #[structopt(long, help = "Include when each entry was created and last updated")]
+    show_dates: bool,
+    #[structopt(
+        long,
+        value_name = "days",
+        help = "Only include entries not changed in this many days, or with no recorded date"
+    )]
+    older_than: Option<i64>,
+    #[structopt(long, help = "Print matching entries as JSON instead of a table")]
+    json: bool,
+    #[structopt(long, requires = "json", help = "Include passwords in the JSON output")]
+    include_passwords: bool,
+}
+
+/// Options for copying part of an entry.
+#[derive(Debug, StructOpt)]
+struct CopyOptions {
+    #[structopt(help = "Name of site/service")]
+    name: String,
+    #[structopt(possible_values = &CopyWhat::variants(), case_insensitive = true, help = "What to copy")]
+    what: CopyWhat,
+    #[structopt(
+        long,
+        alias = "clear",
+        help = "Seconds to wait before clearing the clipboard; 0 to leave it alone [default: 0 for usernames, 45 for anything else]"
+    )]
+    clipboard_timeout: Option<u64>,
+    #[structopt(
+        long,
+        help = "Only copy an entry with exactly this name, never a fuzzy match"
+    )]
+    exact: bool,
+    #[structopt(
+        short,
+        long,
+        parse(from_os_str),
+        help = "Write the value to this file instead of the clipboard; - writes to stdout"
+    )]
+    output: Option<PathBuf>,
+    #[structopt(
+        long,
+        conflicts_with = "output",
+        help = "Print the value to stdout instead of copying it, for piping into other commands"
+    )]
+    stdout: bool,
+    #[structopt(
+        long,
+        conflicts_with_all = &["output", "stdout"],
+        help = "Print the value to stdout if the clipboard can't be used, instead of failing"
+    )]
+    allow_stdout_fallback: bool,
+    #[structopt(
+        long,
+        conflicts_with_all = &["output", "stdout"],
+        help = "Keep running until the timeout, or until enter is pressed if there is none, and clear the clipboard on exit, even if interrupted"
+    )]
+    clipboard_clear_on_exit: bool,
+}
+
 /// CLI subcommands, determining which action to take.
 #[derive(Debug, StructOpt)]
 enum Subcommand {
     #[structopt(about = "Initialize the store")]
-    Init {},
+    Init {
+        #[structopt(long, possible_values = &Kdf::variants(), case_insensitive = true, default_value = "argon2", help = "How to derive the key from the password; none uses a 32-byte password as the key")]
+        kdf: Kdf,
+        #[structopt(long, possible_values = &Cipher::variants(), case_insensitive = true, default_value = "gcm-siv", help = "Cipher to encrypt the store with; gcm-siv stays secure even if a nonce repeats, and chacha20-poly1305 is faster without AES hardware")]
+        cipher: Cipher,
+    },
+    #[structopt(about = "List the named profiles that have a store")]
+    Profiles {
+        #[structopt(
+            long,
+            help = "Ask for each profile's password and show how many entries it has"
+        )]
+        counts: bool,
+    },
+    #[structopt(name = "clear-clip-helper", setting = AppSettings::Hidden)]
+    ClearClip {
+        #[structopt(help = "Seconds to keep the value in the clipboard")]
+        seconds: u64,
+    },
+    #[structopt(about = "Lock ppa so it refuses to run until `ppa unlock`")]
+    Lock {},
+    #[structopt(about = "Check the store password and remove the lock")]
+    Unlock {},
+    #[structopt(about = "Print a shell completion script")]
+    Completions {
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true, help = "Shell to generate completions for")]
+        shell: Shell,
+    },
     #[structopt(about = "Add an entry")]
-    Add {
-        #[structopt(short, long, help = "Name of site/service")]
+    Add(AddOptions),
+    #[structopt(about = "Search through stored entries")]
+    Search(SearchOptions),
+    #[structopt(about = "Copy a username, password, or comments to your clipboard")]
+    Copy(CopyOptions),
+    #[structopt(about = "Print the current TOTP code for an entry", alias = "otp")]
+    Totp {
+        #[structopt(help = "Name of site/service")]
         name: String,
-        #[structopt(short, long, help = "Username")]
-        username: String,
-        #[structopt(short, long, help = "Comments")]
-        comments: Option<String>,
+        #[structopt(short, long, help = "Copy the code to your clipboard as well")]
+        copy: bool,
     },
-    #[structopt(about = "Search through stored entries")]
-    Search {
-        #[structopt(help = "Term to search for; leave blank to list all")]
-        term: Option<String>,
+    #[structopt(about = "Show the full details of matching entries")]
+    Show {
+        #[structopt(help = "Name of site/service")]
+        name: String,
+        #[structopt(short, long, help = "Show the password instead of masking it")]
+        reveal: bool,
+        #[structopt(
+            long,
+            conflicts_with = "reveal",
+            help = "Leave the password out entirely"
+        )]
+        no_password: bool,
     },
-    #[structopt(about = "Copy a username or password to your clipboard")]
-    Copy {
+    #[structopt(about = "List an entry's earlier passwords, most recent first")]
+    History {
         #[structopt(help = "Name of site/service")]
         name: String,
-        #[structopt(possible_values = &CopyWhat::variants(), case_insensitive = true, help = "What to copy")]
-        what: CopyWhat,
     },
+    #[structopt(about = "Generate a random password")]
+    Generate {
+        #[structopt(flatten)]
+        options: GenerateOptions,
+        #[structopt(short, long, help = "Print the password instead of copying it")]
+        print: bool,
+        #[structopt(
+            short,
+            long,
+            requires = "username",
+            help = "Store the password in a new entry with this name"
+        )]
+        name: Option<String>,
+        #[structopt(short, long, requires = "name", help = "Username for the new entry")]
+        username: Option<String>,
+    },
+    #[structopt(about = "Edit an existing entry")]
+    Edit {
+        #[structopt(help = "Name of site/service")]
+        name: String,
+        #[structopt(short, long, help = "New username")]
+        username: Option<String>,
+        #[structopt(short, long, help = "New comments")]
+        comments: Option<String>,
+        #[structopt(short, long, help = "Prompt for a new password")]
+        password: bool,
+    },
+    #[structopt(about = "Interactively update an existing entry")]
+    Update {
+        #[structopt(help = "Name of site/service")]
+        name: String,
+        #[structopt(
+            long,
+            alias = "totp-secret",
+            value_name = "secret",
+            help = "Set the base32 TOTP secret for two-factor codes; an empty one removes it"
+        )]
+        totp: Option<String>,
+    },
+    #[structopt(about = "Rename an entry")]
+    Rename {
+        #[structopt(help = "Current name of site/service")]
+        old: String,
+        #[structopt(help = "New name of site/service")]
+        new: String,
+        #[structopt(short, long, help = "Rename even if an entry with the new name exists")]
+        force: bool,
+    },
+    #[structopt(about = "Export all entries, unencrypted")]
+    Export {
+        #[structopt(short, long, possible_values = &ExportFormat::variants(), case_insensitive = true, default_value = "csv", help = "Format to export as")]
+        format: ExportFormat,
+        #[structopt(
+            short,
+            long,
+            parse(from_os_str),
+            help = "File to write to; leave blank to write to stdout"
+        )]
+        output: Option<PathBuf>,
+        #[structopt(
+            long,
+            alias = "yes-i-know",
+            help = "Confirm that you understand the output will contain plaintext passwords"
+        )]
+        confirm: bool,
+    },
+    #[structopt(about = "Import entries from another password manager")]
+    Import {
+        #[structopt(subcommand)]
+        source: ImportSource,
+    },
+    #[structopt(about = "Copy the encrypted store to a dated backup file")]
+    Backup {
+        #[structopt(
+            long,
+            parse(from_os_str),
+            help = "Directory to put the backup in [default: ~/.ppa_backups]"
+        )]
+        dir: Option<PathBuf>,
+        #[structopt(long, default_value = "10", help = "Number of dated backups to keep")]
+        keep: usize,
+    },
+    #[structopt(about = "List store backups, or swap one back into place")]
+    RestoreBackup {
+        #[structopt(help = "Number of the backup to restore; leave blank to list them")]
+        number: Option<usize>,
+    },
+    #[structopt(about = "List entries not changed in a while, exiting non-zero if there are any")]
+    Age {
+        #[structopt(
+            long,
+            default_value = "90",
+            help = "Report entries not changed in this many days"
+        )]
+        days: i64,
+    },
+    #[structopt(about = "Compare the entries in two stores, like a store and one of its backups")]
+    Diff {
+        #[structopt(long, parse(from_os_str), help = "First store to compare")]
+        store_a: PathBuf,
+        #[structopt(long, parse(from_os_str), help = "Second store to compare")]
+        store_b: PathBuf,
+        #[structopt(
+            long,
+            help = "Also report entries whose passwords differ, without showing them"
+        )]
+        diff_passwords: bool,
+    },
+    #[structopt(about = "Check the store for problems, exiting non-zero if any are found")]
+    Check {},
+    #[structopt(
+        about = "Check that the store can be decrypted and read, without showing any entries"
+    )]
+    Verify {},
+    #[structopt(about = "Count the entries in the store")]
+    Count {},
+    #[structopt(about = "Change the store password")]
+    ChangePassword {},
     #[structopt(about = "Remove an entry")]
     Remove {
         #[structopt(help = "Name of site/service")]
@@ -57,9 +610,12 @@ enum Subcommand {
 }
 
 /// Configure program logging, the level of which is determined by the debug CLI flag.
-fn setup_logging(debug: bool) {
+///
+/// Warnings and errors go to stderr. Other logs go to stdout unless the command
+/// is writing its own output there.
+fn setup_logging(debug: bool, to_stderr: bool) {
     use fern::colors::{Color, ColoredLevelConfig};
-    use log::LevelFilter;
+    use log::{Level, LevelFilter};
 
     let level = if debug {
         LevelFilter::Debug
@@ -68,165 +624,250 @@ fn setup_logging(debug: bool) {
     };
     let colors = ColoredLevelConfig::new().info(Color::Green);
 
-    fern::Dispatch::new()
+    let dispatch = fern::Dispatch::new()
         .format(move |out, message, record| {
             out.finish(format_args!("{} {}", colors.color(record.level()), message))
         })
-        .level(level)
-        .chain(std::io::stdout())
+        .level(level);
+    let warnings = fern::Dispatch::new()
+        .filter(|metadata| metadata.level() <= Level::Warn)
+        .chain(std::io::stderr());
+    let others = fern::Dispatch::new().filter(|metadata| metadata.level() > Level::Warn);
+    let others = if to_stderr {
+        others.chain(std::io::stderr())
+    } else {
+        others.chain(std::io::stdout())
+    };
+    dispatch
+        .chain(warnings)
+        .chain(others)
         .apply()
         .expect("[FATAL] Could not set up logger");
 }
 
-/// Prompt the user for a password, optionally requiring confirmation and length requirement.
-fn prompt_password(confirm: bool, require_length: bool) -> String {
-    let prompt_theme = ColorfulTheme::default();
-    let mut prompt = Password::with_theme(&prompt_theme);
-    prompt.with_prompt("Store password");
-    if confirm {
-        prompt.with_confirmation("", "");
-    }
-    loop {
-        let encryption_password = match prompt.interact() {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Could not prompt for password: {}", e);
-                process::exit(1);
-            }
-        };
-        if !require_length || encryption_password.len() == 32 {
-            return encryption_password;
+/// Keep only one of the store path and the profile, which can each come from
+/// a flag or an environment variable.
+///
+/// A flag beats an environment variable, so `PPA_PROFILE=work ppa --store x`
+/// uses the store at `x`, and if both come from the environment, `PPA_STORE`
+/// wins. Only passing both flags is an error.
+fn choose_store_or_profile(args: &mut Options, matches: &ArgMatches) {
+    if args.store.is_none() || args.profile.is_none() {
+        return;
+    }
+    let store_flag = matches.occurrences_of("store") > 0;
+    let profile_flag = matches.occurrences_of("profile") > 0;
+    match (store_flag, profile_flag) {
+        (true, true) => {
+            error!("--store cannot be used with --profile");
+            process::exit(1);
+        }
+        (false, true) => {
+            debug!("Ignoring PPA_STORE for --profile");
+            args.store = None;
+        }
+        (_, false) => {
+            debug!("Ignoring PPA_PROFILE for the store path");
+            args.profile = None;
         }
-        error!("Password must be 32 characters long");
     }
 }
 
 /// Entry point
 fn main() {
-    let args = Options::from_args();
-    setup_logging(args.debug);
-
-    if let Some(Subcommand::Init {}) = args.command {
-        match util::store_exists() {
-            Ok(true) => info!("Store already exists!"),
-            Err(e) => {
-                error!("Could not check for store file: {}", e);
-                process::exit(1);
-            }
-            _ => {
-                // continue
-            }
+    let config = config::load();
+    let matches = Options::clap().get_matches();
+    let mut args = Options::from_clap(&matches);
+    let output_on_stdout = args
+        .command
+        .as_ref()
+        .is_some_and(Subcommand::writes_to_stdout);
+    setup_logging(args.debug, output_on_stdout);
+    choose_store_or_profile(&mut args, &matches);
+    let osc52 = args.osc52 || clip::osc52_detected();
+
+    if let Some(Subcommand::ClearClip { seconds }) = args.command {
+        hold_clipboard(seconds, osc52);
+        return;
+    }
+
+    if let Some(Subcommand::Completions { shell }) = args.command {
+        Options::clap().gen_completions_to("ppa", shell, &mut io::stdout());
+        return;
+    }
+
+    if let Some(Subcommand::Lock {}) = args.command {
+        commands::lock();
+        return;
+    }
+
+    let unlocking = matches!(args.command, Some(Subcommand::Unlock {}));
+    match ppa::is_locked() {
+        Ok(false) => {}
+        Ok(true) if unlocking => {}
+        Ok(true) => {
+            error!("Store is locked; run `ppa unlock` to proceed");
+            process::exit(1);
         }
-        let encryption_password = prompt_password(true, true);
-        match util::write_store(&[], &encryption_password) {
-            Ok(()) => info!("Store created"),
-            Err(e) => {
-                error!("Could not create store: {}", e);
-                process::exit(1);
-            }
+        Err(e) => {
+            error!("Could not check for the lock file: {}", e);
+            process::exit(1);
         }
-        return;
     }
 
-    let encryption_password = prompt_password(false, true);
-    let mut entries = match util::load_store(&encryption_password) {
-        Ok(e) => e,
+    ppa::set_read_only(args.read_only);
+    if args.read_only && args.command.as_ref().is_some_and(Subcommand::writes_store) {
+        error!("This command changes the store, which --read-only doesn't allow");
+        process::exit(1);
+    }
+
+    let config = match config {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Could not read config file: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let store = args.store.as_deref().or(config.store_path.as_deref());
+    let profile = args
+        .profile
+        .as_deref()
+        .filter(|name| *name != ppa::DEFAULT_PROFILE);
+    let store_path = match profile {
+        Some(name) => ppa::profile_path(name),
+        None => ppa::path_to_store(store),
+    };
+    let store_path = match store_path {
+        Ok(p) => p,
         Err(e) => {
-            error!("Could not load store: {}", e);
+            error!("Could not determine store path: {}", e);
             process::exit(1);
         }
     };
+    debug!("Using store at {}", store_path.display());
+
+    match &args.command {
+        Some(Subcommand::Profiles { counts }) => {
+            let default_store = if profile.is_some() {
+                ppa::path_to_store(store).ok()
+            } else {
+                Some(store_path)
+            };
+            commands::profiles(default_store, &args.password, *counts);
+            return;
+        }
+        Some(Subcommand::Init { kdf, cipher }) => {
+            commands::init(
+                &store_path,
+                profile.is_some(),
+                &args.password,
+                *kdf,
+                *cipher,
+            );
+            return;
+        }
+        Some(Subcommand::Backup { dir, keep }) => {
+            commands::backup(&store_path, dir.clone(), *keep);
+            return;
+        }
+        Some(Subcommand::RestoreBackup { number }) => {
+            commands::restore_backup(&store_path, *number);
+            return;
+        }
+        Some(Subcommand::Diff {
+            store_a,
+            store_b,
+            diff_passwords,
+        }) => {
+            commands::diff(store_a, store_b, *diff_passwords);
+            return;
+        }
+        Some(Subcommand::Generate {
+            options,
+            print,
+            name: None,
+            ..
+        }) => {
+            commands::generate(options, *print, osc52);
+            return;
+        }
+        Some(Subcommand::Export { confirm: false, .. }) => {
+            warn!("Exports contain every password in PLAINTEXT");
+            if !prompt::prompt_confirm("Export anyway?") {
+                info!("Export cancelled");
+                return;
+            }
+        }
+        _ => {}
+    }
+
+    let (encryption_password, mut entries) =
+        unlock::unlock_store(&store_path, &args.password, !args.no_retry);
+    unlock::check_permissions(&store_path, args.read_only);
+    let writes = args.command.as_ref().is_some_and(Subcommand::writes_store);
+    let store_path = if writes && store.is_none() && profile.is_none() {
+        unlock::offer_store_move(store_path)
+    } else {
+        store_path
+    };
+    let context = commands::Context {
+        store_path,
+        encryption_password,
+        backups: args.backups,
+        password_history: config
+            .password_history
+            .unwrap_or(ppa::DEFAULT_PASSWORD_HISTORY),
+        clipboard_timeout: config
+            .clipboard_timeout
+            .unwrap_or(config::DEFAULT_CLIPBOARD_TIMEOUT),
+        osc52,
+    };
 
     match args.command {
-        Some(Subcommand::Add {
+        Some(Subcommand::Add(options)) => commands::add(&context, &mut entries, options),
+        Some(Subcommand::Search(options)) => commands::search(&entries, options),
+        Some(Subcommand::Copy(options)) => commands::copy(&context, &mut entries, options),
+        Some(Subcommand::Totp { name, copy }) => commands::totp(&context, &entries, &name, copy),
+        Some(Subcommand::Show {
+            name,
+            reveal,
+            no_password,
+        }) => commands::show(&entries, &name, reveal, no_password),
+        Some(Subcommand::History { name }) => commands::history(&entries, &name),
+        Some(Subcommand::Generate {
+            options,
+            print,
+            name: Some(name),
+            username,
+        }) => commands::generate_entry(&context, &mut entries, &options, print, name, username),
+        Some(Subcommand::Edit {
             name,
             username,
             comments,
-        }) => {
-            debug!("Adding new entry");
-            let password = prompt_password(true, false);
-            entries.push(Entry {
-                name,
-                username,
-                password,
-                comments: comments.unwrap_or_default(),
-            });
-            if let Err(e) = util::write_store(&entries, &encryption_password) {
-                error!("Could not save store: {}", e);
-                process::exit(1);
-            }
-            info!("Entry added");
+            password,
+        }) => commands::edit(&context, &mut entries, &name, username, comments, password),
+        Some(Subcommand::Update { name, totp }) => {
+            commands::update(&context, &mut entries, &name, totp.as_deref())
         }
-        Some(Subcommand::Search { term }) => {
-            if entries.is_empty() {
-                info!("Store is empty");
-                return;
-            }
-            let matcher = SkimMatcherV2::default();
-            let mut table = Table::new();
-            table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-            table.set_titles(row!["Name", "Username", "Comments"]);
-            for entry in entries {
-                match term.as_ref() {
-                    Some(t) => {
-                        if matcher.fuzzy_match(&entry.name, t).is_some() {
-                            table.add_row(row![entry.name, entry.username, entry.comments]);
-                        }
-                    }
-                    None => {
-                        table.add_row(row![entry.name, entry.username, entry.comments]);
-                    }
-                }
-            }
-            let match_count = table.row_iter().count();
-            debug!("Found {} matching entries", match_count);
-            if match_count > 0 {
-                table.printstd();
-            } else {
-                warn!("No matching entries");
-            }
+        Some(Subcommand::Rename { old, new, force }) => {
+            commands::rename(&context, &mut entries, &old, new, force)
         }
-        Some(Subcommand::Copy { name, what }) => {
-            for entry in entries {
-                if entry.name.to_lowercase() == name.to_lowercase() {
-                    let mut clipboard: ClipboardContext = match ClipboardProvider::new() {
-                        Ok(c) => c,
-                        Err(e) => {
-                            error!("Could not set up clipboard context: {}", e);
-                            process::exit(1);
-                        }
-                    };
-                    let (copy_value, copy_message) = match what {
-                        CopyWhat::Username => (entry.username, "username"),
-                        CopyWhat::Password => (entry.password, "password"),
-                    };
-                    if let Err(e) = clipboard.set_contents(copy_value) {
-                        error!("Could not copy value to your clipboard: {}", e);
-                        process::exit(1);
-                    }
-                    info!("Copied the {} to your clipboard", copy_message);
-                    return;
-                }
-            }
-            warn!("Could not find matching entry");
-        }
-        Some(Subcommand::Remove { name }) => {
-            let start_len = entries.len();
-            entries = entries
-                .iter()
-                .filter(|&entry| entry.name.to_lowercase() != name.to_lowercase())
-                .cloned()
-                .collect();
-            if entries.len() == start_len {
-                warn!("could not find matching entry");
-            } else {
-                if let Err(e) = util::write_store(&entries, &encryption_password) {
-                    error!("Could not save store: {}", e);
-                    process::exit(1);
-                }
-                info!("Entry removed");
-            }
+        Some(Subcommand::Export { format, output, .. }) => {
+            commands::export(&entries, &format, output.as_deref())
+        }
+        Some(Subcommand::Import { source }) => commands::import(&context, &mut entries, source),
+        Some(Subcommand::Age { days }) => commands::age(&entries, days),
+        Some(Subcommand::Check {}) => commands::check(&context, &entries),
+        Some(Subcommand::Unlock {}) => commands::unlock(),
+        Some(Subcommand::Verify {}) => {
+            // Loading already exited with the error if the store was unreadable
+            info!("Store is readable: {} entries", entries.len());
         }
+        Some(Subcommand::Count {}) => commands::count(&context, &entries),
+        Some(Subcommand::ChangePassword {}) => commands::change_password(&context, &entries),
+        Some(Subcommand::Remove { name }) => commands::remove(&context, &mut entries, &name),
+        None => commands::session(&context, &mut entries, args.read_only),
         _ => {
             error!("Unrecognized subcommand");
             process::exit(1);
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Local, Utc};
+use log::info;
+use ppa::Entry;
+use prettytable::{format, row, Cell, Table};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+/// Write a value to a file readable only by its owner, or to stdout if the path is `-`.
+pub(crate) fn write_output(path: &Path, content: &str) -> anyhow::Result<()> {
+    if path == Path::new("-") {
+        io::stdout().write_all(content.as_bytes())?;
+        return Ok(());
+    }
+    let mut file = ppa::create_private_file(path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+/// Print a table of entries, leaving out their passwords.
+///
+/// The created and updated timestamps are included if `show_dates` is set.
+pub(crate) fn print_entries(entries: &[&Entry], show_dates: bool) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    let mut titles = vec!["Name", "Username", "URL", "Tags", "Comments"];
+    if show_dates {
+        titles.extend(["Created", "Updated"]);
+    }
+    table.set_titles(titles.into_iter().map(Cell::new).collect());
+    for entry in entries {
+        let mut cells = vec![
+            entry.name.clone(),
+            entry.username.clone(),
+            entry.url.clone().unwrap_or_default(),
+            entry.tags.join(", "),
+            entry.comments.clone(),
+        ];
+        if show_dates {
+            cells.extend([
+                format_timestamp(entry.created_at),
+                format_timestamp(entry.updated_at),
+            ]);
+        }
+        table.add_row(cells.iter().map(|cell| Cell::new(cell)).collect());
+    }
+    table.printstd();
+}
+
+/// Print a table of entries with how many days ago each was changed.
+pub(crate) fn print_entry_ages(entries: &[(&Entry, Option<i64>)]) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row![
+        "Name",
+        "Username",
+        "URL",
+        "Last changed",
+        "Age (days)"
+    ]);
+    for (entry, age) in entries {
+        table.add_row(row![
+            entry.name,
+            entry.username,
+            entry.url.as_deref().unwrap_or_default(),
+            format_timestamp(entry.last_changed()),
+            age.map_or_else(|| "unknown".to_owned(), |days| days.to_string())
+        ]);
+    }
+    table.printstd();
+}
+
+/// An entry as printed by `search --json`, with the password left out unless asked for.
+#[derive(Serialize)]
+struct SearchResult<'a> {
+    name: &'a str,
+    username: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    url: Option<&'a str>,
+    comments: &'a str,
+    tags: &'a [String],
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// Print the entries to stdout as a JSON array.
+pub(crate) fn print_entries_json(
+    entries: &[&Entry],
+    include_passwords: bool,
+) -> serde_json::Result<()> {
+    let results: Vec<SearchResult> = entries
+        .iter()
+        .map(|entry| SearchResult {
+            name: &entry.name,
+            username: &entry.username,
+            password: Some(entry.password.as_str()).filter(|_| include_passwords),
+            url: entry.url.as_deref(),
+            comments: &entry.comments,
+            tags: &entry.tags,
+            created_at: entry.created_at,
+            updated_at: entry.updated_at,
+        })
+        .collect();
+    serde_json::to_writer(io::stdout(), &results)?;
+    println!();
+    Ok(())
+}
+
+/// How to display passwords in `show`.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum PasswordDisplay {
+    Masked,
+    Revealed,
+    Hidden,
+}
+
+/// Format an entry timestamp in local time, or a dash if it wasn't recorded.
+fn format_timestamp(timestamp: Option<DateTime<Utc>>) -> String {
+    timestamp.map_or_else(
+        || "-".to_owned(),
+        |t| {
+            DateTime::<Local>::from(t)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        },
+    )
+}
+
+/// Print a table of every field of the entries.
+pub(crate) fn print_entry_details(entries: &[&Entry], password: PasswordDisplay) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    let mut titles = vec!["Name", "Username"];
+    if password != PasswordDisplay::Hidden {
+        titles.push("Password");
+    }
+    titles.extend(["URL", "Tags", "Comments", "Created", "Updated"]);
+    table.set_titles(titles.into_iter().map(Cell::new).collect());
+    for entry in entries {
+        let mut cells = vec![entry.name.clone(), entry.username.clone()];
+        match password {
+            PasswordDisplay::Masked => cells.push("****".to_owned()),
+            PasswordDisplay::Revealed => cells.push(entry.password.clone()),
+            PasswordDisplay::Hidden => {}
+        }
+        cells.extend([
+            entry.url.clone().unwrap_or_default(),
+            entry.tags.join(", "),
+            entry.comments.clone(),
+            format_timestamp(entry.created_at),
+            format_timestamp(entry.updated_at),
+        ]);
+        table.add_row(cells.iter().map(|cell| Cell::new(cell)).collect());
+    }
+    table.printstd();
+}
+
+/// Print the differences between two stores side by side.
+pub(crate) fn print_store_diff(diff: &ppa::StoreDiff) {
+    if diff.is_empty() {
+        info!("Both stores have the same entries");
+        return;
+    }
+    let changed: Vec<String> = diff
+        .changed
+        .iter()
+        .map(|(name, fields)| format!("{} ({})", name, fields.join(", ")))
+        .collect();
+    let rows = diff.only_a.len().max(diff.only_b.len()).max(changed.len());
+    let cell = |column: &[String], row: usize| column.get(row).cloned().unwrap_or_default();
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row!["Only in A", "Only in B", "Different"]);
+    for row in 0..rows {
+        table.add_row(row![
+            cell(&diff.only_a, row),
+            cell(&diff.only_b, row),
+            cell(&changed, row)
+        ]);
+    }
+    table.printstd();
+}
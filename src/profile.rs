@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::{fs, path::PathBuf};
+
+/// Profile used when none is given, whose store is the one `path_to_store` picks.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A named store kept in the profiles directory.
+#[derive(Debug)]
+pub struct Profile {
+    /// Name given to `--profile`
+    pub name: String,
+    /// Where the profile's store is on disk
+    pub path: PathBuf,
+}
+
+/// Return the directory that named profiles' stores go in, `~/.ppa`.
+pub fn profiles_dir() -> Result<PathBuf> {
+    Ok(home::home_dir()
+        .ok_or_else(|| anyhow!("Could not find user's home directory"))?
+        .join(".ppa"))
+}
+
+/// Create the profiles directory if it doesn't exist yet, readable only by its owner.
+#[cfg(unix)]
+pub fn create_profiles_dir() -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    let dir = profiles_dir()?;
+    debug!("Creating {}", dir.display());
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    Ok(())
+}
+
+/// Create the profiles directory if it doesn't exist yet.
+#[cfg(not(unix))]
+pub fn create_profiles_dir() -> Result<()> {
+    let dir = profiles_dir()?;
+    debug!("Creating {}", dir.display());
+    fs::create_dir_all(dir)?;
+    Ok(())
+}
+
+/// Return the store path for a named profile, like `~/.ppa/work.bin`.
+///
+/// The default profile isn't kept here; its store is wherever `path_to_store` puts it.
+pub fn profile_path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && !name.contains(&['/', '\\'][..])
+        && name != DEFAULT_PROFILE;
+    if !valid {
+        return Err(anyhow!("Not a valid profile name: {:?}", name));
+    }
+    Ok(profiles_dir()?.join(format!("{}.bin", name)))
+}
+
+/// List the named profiles that have a store, sorted by name.
+pub fn list_profiles() -> Result<Vec<Profile>> {
+    let dir = profiles_dir()?;
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut profiles: Vec<Profile> = fs::read_dir(dir)?
+        .filter_map(|dir_entry| dir_entry.ok().map(|d| d.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "bin"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_owned();
+            Some(Profile { name, path }).filter(|p| profile_path(&p.name).is_ok())
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
@@ -0,0 +1,149 @@
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password};
+use log::{debug, error, info, warn};
+use std::{io, process};
+
+/// Read one line from stdin, without its line ending.
+pub(crate) fn read_stdin_line() -> String {
+    let mut line = String::new();
+    match io::stdin().read_line(&mut line) {
+        Ok(0) => {
+            error!("Expected a line on stdin, but it is empty");
+            process::exit(1);
+        }
+        Ok(_) => line.trim_end_matches(&['\r', '\n'][..]).to_owned(),
+        Err(e) => {
+            error!("Could not read from stdin: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Prompt the user for a password, optionally requiring confirmation.
+///
+/// If stdin isn't a terminal, a single line is read from it instead.
+pub(crate) fn prompt_password(text: &str, confirm: bool) -> String {
+    if !atty::is(atty::Stream::Stdin) {
+        debug!("Reading {} from stdin", text.to_lowercase());
+        return read_stdin_line();
+    }
+    let prompt_theme = ColorfulTheme::default();
+    let mut prompt = Password::with_theme(&prompt_theme);
+    prompt.with_prompt(text);
+    if confirm {
+        let confirmation = format!("Confirm {}", text.to_lowercase());
+        prompt.with_confirmation(confirmation, "The passwords don't match");
+    }
+    match prompt.interact() {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not prompt for password: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Prompt the user for a new store password until they give one that is long enough.
+pub(crate) fn prompt_new_store_password(text: &str) -> String {
+    loop {
+        let password = prompt_password(text, true);
+        match ppa::check_password_length(&password) {
+            Ok(()) => return password,
+            Err(e) if !atty::is(atty::Stream::Stdin) => {
+                error!("{}", e);
+                process::exit(1);
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+/// Prompt the user for an entry's password, reporting how strong it is.
+///
+/// Passwords scoring below `min_strength` are rejected and the user is asked again.
+pub(crate) fn prompt_entry_password(min_strength: Option<u8>, user_inputs: &[&str]) -> String {
+    loop {
+        let password = prompt_password("Entry password", true);
+        let score = ppa::password_strength(&password, user_inputs);
+        info!(
+            "Password strength: {} ({}/4)",
+            ppa::strength_label(score),
+            score
+        );
+        match min_strength {
+            Some(min) if score < min => {
+                let message = format!("Password strength is below the minimum of {}/4", min);
+                if !atty::is(atty::Stream::Stdin) {
+                    error!("{}", message);
+                    process::exit(1);
+                }
+                warn!("{}; try another", message);
+            }
+            _ => {
+                if score < ppa::MIN_RECOMMENDED_STRENGTH {
+                    warn!("This password is weak and could be guessed easily");
+                }
+                return password;
+            }
+        }
+    }
+}
+
+/// Prompt the user for a new password, returning `None` if they leave it blank.
+pub(crate) fn prompt_new_password() -> Option<String> {
+    let prompt_theme = ColorfulTheme::default();
+    let mut prompt = Password::with_theme(&prompt_theme);
+    prompt
+        .with_prompt("New password (leave blank to keep)")
+        .with_confirmation("", "")
+        .allow_empty_password(true);
+    match prompt.interact() {
+        Ok(p) if p.is_empty() => None,
+        Ok(p) => Some(p),
+        Err(e) => {
+            error!("Could not prompt for password: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Ask the user a yes/no question, defaulting to no.
+pub(crate) fn prompt_confirm(prompt: &str) -> bool {
+    let prompt_theme = ColorfulTheme::default();
+    match Confirm::with_theme(&prompt_theme)
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not prompt for confirmation: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Prompt the user for a line of text, pre-populated with the current value.
+pub(crate) fn prompt_input(prompt: &str, current: &str) -> String {
+    let prompt_theme = ColorfulTheme::default();
+    match Input::<String>::with_theme(&prompt_theme)
+        .with_prompt(prompt)
+        .with_initial_text(current)
+        .allow_empty(true)
+        .interact()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Could not prompt for input: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Wait for the user to press enter.
+pub(crate) fn wait_for_enter(prompt: &str) {
+    eprint!("{} ", prompt);
+    if let Err(e) = io::stdin().read_line(&mut String::new()) {
+        error!("Could not read from stdin: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::Entry;
+use clap::arg_enum;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+
+arg_enum! {
+    /// Entry fields that searches can match against.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum SearchField {
+        Name,
+        Username,
+        Url,
+        Comments,
+        Tags,
+    }
+}
+
+/// Get the best fuzzy match score of the term across the entry's fields.
+///
+/// Every field is searched if none are given. Returns `None` if no field matches.
+fn search_score(
+    matcher: &SkimMatcherV2,
+    entry: &Entry,
+    term: &str,
+    fields: &[SearchField],
+) -> Option<i64> {
+    let searched = |field| fields.is_empty() || fields.contains(&field);
+    let tags = entry.tags.join(" ");
+    let values = [
+        (SearchField::Name, Some(entry.name.as_str())),
+        (SearchField::Username, Some(entry.username.as_str())),
+        (SearchField::Url, entry.url.as_deref()),
+        (SearchField::Comments, Some(entry.comments.as_str())),
+        (SearchField::Tags, Some(tags.as_str())),
+    ];
+    values
+        .iter()
+        .filter(|(field, _)| searched(*field))
+        .filter_map(|(_, value)| value.and_then(|value| matcher.fuzzy_match(value, term)))
+        .max()
+}
+
+/// Return the entries that fuzzy-match the term in any of the fields, best match first.
+///
+/// Every field is searched if none are given, and without a term every entry
+/// matches, keeping its order.
+pub fn search_entries<'a, I>(
+    entries: I,
+    term: Option<&str>,
+    fields: &[SearchField],
+) -> Vec<&'a Entry>
+where
+    I: IntoIterator<Item = &'a Entry>,
+{
+    let matcher = SkimMatcherV2::default();
+    let mut scored: Vec<(i64, &Entry)> = entries
+        .into_iter()
+        .filter_map(|entry| match term {
+            Some(t) => search_score(&matcher, entry, t, fields).map(|score| (score, entry)),
+            None => Some((0, entry)),
+        })
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Return the indices of the entries whose name fuzzy-matches the given one, best match first.
+pub fn fuzzy_matching_indices(entries: &[Entry], name: &str) -> Vec<usize> {
+    let matcher = SkimMatcherV2::default();
+    let mut scored: Vec<(i64, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            matcher
+                .fuzzy_match(&entry.name, name)
+                .map(|score| (score, index))
+        })
+        .collect();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored.into_iter().map(|(_, index)| index).collect()
+}
@@ -0,0 +1,278 @@
+use crate::{
+    clip::Clipboard,
+    lookup::{fuzzy_entry_index, Ambiguous},
+    output::{print_entries, print_entry_details, PasswordDisplay},
+    prompt::{prompt_password, wait_for_enter},
+    CopyWhat,
+};
+use clap::AppSettings;
+use log::{error, info, warn};
+use ppa::Entry;
+use std::{
+    io::{self, Write},
+    iter,
+    path::Path,
+    thread,
+    time::Duration,
+};
+use structopt::StructOpt;
+
+/// Commands that can be run in the interactive session.
+#[derive(Debug, StructOpt)]
+#[structopt(name = "ppa", global_settings = &[AppSettings::DisableVersion, AppSettings::VersionlessSubcommands])]
+enum SessionCommand {
+    #[structopt(about = "Search entries by name, username, URL, comments, or tags")]
+    Search {
+        #[structopt(help = "Term to search for; lists every entry if left out")]
+        term: Option<String>,
+    },
+    #[structopt(about = "Copy a value from an entry into your clipboard")]
+    Copy {
+        #[structopt(help = "Name of site/service")]
+        name: String,
+        #[structopt(possible_values = &CopyWhat::variants(), case_insensitive = true, default_value = "password", help = "What to copy")]
+        what: CopyWhat,
+    },
+    #[structopt(about = "Show the full details of an entry")]
+    Show {
+        #[structopt(help = "Name of site/service")]
+        name: String,
+        #[structopt(short, long, help = "Show the password instead of masking it")]
+        reveal: bool,
+    },
+    #[structopt(about = "Add an entry")]
+    Add {
+        #[structopt(help = "Name of site/service")]
+        name: String,
+        #[structopt(help = "Username")]
+        username: String,
+        #[structopt(short, long, help = "Comments")]
+        comments: Option<String>,
+    },
+    #[structopt(about = "Leave the session", alias = "exit")]
+    Quit {},
+}
+
+/// What the session needs to save the store after a change.
+pub(crate) struct Session<'a> {
+    pub(crate) store_path: &'a Path,
+    pub(crate) encryption_password: &'a str,
+    pub(crate) backups: usize,
+    /// Seconds before a copied value is cleared from the clipboard, or 0 to leave it
+    pub(crate) clipboard_timeout: u64,
+    /// Copy to the terminal's clipboard with OSC 52 instead of the system's
+    pub(crate) osc52: bool,
+    /// Refuse to add entries, since the store mustn't be written
+    pub(crate) read_only: bool,
+}
+
+/// Split a line into words, keeping text inside double quotes together.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Clear the clipboard after the timeout if it still holds the value, without blocking the session.
+fn clear_clipboard_later(mut clipboard: Clipboard, value: String, seconds: u64) {
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(seconds));
+        if clipboard
+            .get_contents()
+            .is_ok_and(|current| current.is_none_or(|current| current == value))
+        {
+            let _ = clipboard.set_contents(String::new());
+        }
+    });
+}
+
+/// Clear the clipboard now if it still holds the value, or if it can't be read back.
+fn clear_clipboard_now(value: &str, osc52: bool) {
+    let cleared = Clipboard::new(osc52).and_then(|mut clipboard| {
+        if clipboard
+            .get_contents()?
+            .is_none_or(|current| current == value)
+        {
+            clipboard.set_contents(String::new())?;
+        }
+        Ok(())
+    });
+    if let Err(e) = cleared {
+        warn!("Could not clear your clipboard: {}", e);
+    }
+}
+
+/// Read commands and run them against the unlocked store until the user quits.
+///
+/// Changes are written to the store as soon as they're made.
+pub(crate) fn run(session: &Session, entries: &mut Vec<Entry>) {
+    info!("Store unlocked; type `help` for commands and `quit` to leave");
+    let mut last_copied: Option<String> = None;
+    let mut kept_clipboard: Option<Clipboard> = None;
+    loop {
+        print!("ppa> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => {
+                println!();
+                break;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!("Could not read from stdin: {}", e);
+                break;
+            }
+        }
+        let words = split_words(&line);
+        if words.is_empty() {
+            continue;
+        }
+        let command =
+            match SessionCommand::from_iter_safe(iter::once("ppa".to_owned()).chain(words)) {
+                Ok(c) => c,
+                Err(e) => {
+                    println!("{}", e.message);
+                    continue;
+                }
+            };
+        match command {
+            SessionCommand::Search { term } => {
+                let matching = ppa::search_entries(entries.iter(), term.as_deref(), &[]);
+                if matching.is_empty() {
+                    warn!("No matching entries");
+                } else {
+                    print_entries(&matching, false);
+                }
+            }
+            SessionCommand::Copy { name, what } => {
+                let entry = match fuzzy_entry_index(entries, &name) {
+                    Ok(Some(index)) => &entries[index],
+                    Ok(None) => {
+                        warn!("Could not find matching entry");
+                        continue;
+                    }
+                    Err(Ambiguous) => {
+                        warn!("Several entries match; use the exact name");
+                        continue;
+                    }
+                };
+                let (value, field) = match what {
+                    CopyWhat::Username => (entry.username.clone(), "username"),
+                    CopyWhat::Password | CopyWhat::Both => (entry.password.clone(), "password"),
+                    CopyWhat::Comments => (entry.comments.clone(), "comments"),
+                };
+                if value.is_empty() {
+                    warn!("'{}' has no {}; nothing copied", entry.name, field);
+                    continue;
+                }
+                let copied = Clipboard::new(session.osc52).and_then(|mut clipboard| {
+                    if let CopyWhat::Both = what {
+                        clipboard.set_contents(entry.username.clone())?;
+                        info!("Copied the username to your clipboard");
+                        wait_for_enter("Press enter to copy the password");
+                    }
+                    clipboard.set_contents(value.clone())?;
+                    Ok(clipboard)
+                });
+                let clipboard = match copied {
+                    Ok(c) => c,
+                    Err(e) => {
+                        error!("Could not copy to your clipboard: {}", e);
+                        continue;
+                    }
+                };
+                info!(
+                    "Copied the {} for '{}' to your clipboard",
+                    field, entry.name
+                );
+                if session.clipboard_timeout > 0 {
+                    info!(
+                        "Clearing the clipboard in {} seconds",
+                        session.clipboard_timeout
+                    );
+                    clear_clipboard_later(clipboard, value.clone(), session.clipboard_timeout);
+                } else {
+                    // The clipboard has to stay alive for X11 to keep serving its contents
+                    kept_clipboard = Some(clipboard);
+                }
+                last_copied = Some(value);
+            }
+            SessionCommand::Show { name, reveal } => {
+                let matching: Vec<&Entry> = ppa::matching_indices(entries, &name)
+                    .into_iter()
+                    .map(|index| &entries[index])
+                    .collect();
+                if matching.is_empty() {
+                    warn!("Could not find matching entry");
+                    continue;
+                }
+                let password = if reveal {
+                    PasswordDisplay::Revealed
+                } else {
+                    PasswordDisplay::Masked
+                };
+                print_entry_details(&matching, password);
+            }
+            SessionCommand::Add {
+                name,
+                username,
+                comments,
+            } => {
+                if session.read_only {
+                    error!("The store is read-only; restart without --read-only to add entries");
+                    continue;
+                }
+                if !ppa::matching_indices(entries, &name).is_empty() {
+                    error!("An entry named '{}' already exists", name);
+                    continue;
+                }
+                let password = prompt_password("Entry password", true);
+                let mut entry = Entry::new(name, username, password);
+                entry.comments = comments.unwrap_or_default();
+                entries.push(entry);
+                match ppa::write_store(
+                    session.store_path,
+                    entries,
+                    session.encryption_password,
+                    session.backups,
+                ) {
+                    Ok(()) => info!("Entry added"),
+                    Err(e) => {
+                        entries.pop();
+                        error!("Could not save store: {}", e);
+                    }
+                }
+            }
+            SessionCommand::Quit {} => break,
+        }
+    }
+    // The terminal keeps what was copied with OSC 52 after ppa exits, so only clear it if asked to
+    if let Some(value) = last_copied.filter(|_| !session.osc52 || session.clipboard_timeout > 0) {
+        clear_clipboard_now(&value, session.osc52);
+    }
+    drop(kept_clipboard);
+}
@@ -0,0 +1,790 @@
+use crate::{backup, Entry};
+use aes_gcm::{
+    aead::{
+        generic_array::{typenum::consts::U12, GenericArray},
+        Aead, NewAead,
+    },
+    Aes256Gcm,
+};
+use aes_gcm_siv::Aes256GcmSiv;
+use anyhow::{anyhow, Result};
+use chacha20poly1305::ChaCha20Poly1305;
+use clap::arg_enum;
+use log::{debug, info};
+use rand::{thread_rng, Rng};
+use sha2::Sha256;
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use zeroize::Zeroizing;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+const KDF_PARAMS_LENGTH: usize = 8;
+/// A KDF id byte followed by that KDF's parameters.
+const KDF_HEADER_LENGTH: usize = 1 + KDF_PARAMS_LENGTH;
+
+/// Bytes every store file starts with.
+const MAGIC: &[u8; 4] = b"PPA1";
+/// Version of the layout that follows the magic bytes.
+///
+/// Version 1 always used Argon2id and AES-256-GCM, version 2 records which KDF
+/// the store uses, and version 3 records its cipher as well.
+const FORMAT_VERSION: u8 = 3;
+
+/// Cipher for new stores.
+const DEFAULT_CIPHER: Cipher = Cipher::AesGcmSiv;
+
+/// PBKDF2-HMAC-SHA256 rounds for new stores, per OWASP's Password Storage Cheat Sheet.
+const PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Shortest store password accepted when creating or changing one, in characters.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Check that a new store password is long enough.
+///
+/// Length is counted in characters rather than bytes, since any number of
+/// bytes can go through the KDF.
+pub fn check_password_length(password: &str) -> Result<()> {
+    let characters = password.chars().count();
+    if characters >= MIN_PASSWORD_LENGTH {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Store password must be at least {} characters, but it has {} ({} bytes)",
+        MIN_PASSWORD_LENGTH,
+        characters,
+        password.len()
+    ))
+}
+
+/// Argon2id cost parameters, recorded in the store header so they can be raised later.
+#[derive(Debug, Clone, Copy)]
+struct KdfParams {
+    /// Memory cost in KiB
+    mem_cost: u32,
+    /// Number of passes
+    time_cost: u32,
+}
+
+/// Parameters used for new stores, and for stores written before they were recorded.
+const DEFAULT_KDF_PARAMS: KdfParams = KdfParams {
+    mem_cost: 19456,
+    time_cost: 2,
+};
+
+impl KdfParams {
+    /// Serialize as little-endian mem_cost|time_cost.
+    fn to_bytes(self) -> [u8; KDF_PARAMS_LENGTH] {
+        let mut bytes = [0; KDF_PARAMS_LENGTH];
+        bytes[..4].copy_from_slice(&self.mem_cost.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.time_cost.to_le_bytes());
+        bytes
+    }
+
+    /// Read parameters from a header, rejecting values no store would have been written with.
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < KDF_PARAMS_LENGTH {
+            return None;
+        }
+        let read = |range: std::ops::Range<usize>| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[range]);
+            u32::from_le_bytes(word)
+        };
+        let params = Self {
+            mem_cost: read(0..4),
+            time_cost: read(4..8),
+        };
+        let sane = (8..=262_144).contains(&params.mem_cost) && (1..=10).contains(&params.time_cost);
+        Some(params).filter(|_| sane)
+    }
+}
+
+arg_enum! {
+    /// Ways of turning the store password into the encryption key.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Kdf {
+        Argon2,
+        Pbkdf2,
+        None,
+    }
+}
+
+/// The KDF a store uses along with its parameters, as recorded in the store header.
+#[derive(Debug, Clone, Copy)]
+enum KeyDerivation {
+    /// The password is used directly as the key, and so must be 32 bytes
+    Raw,
+    Argon2id(KdfParams),
+    Pbkdf2 {
+        iterations: u32,
+    },
+}
+
+impl KeyDerivation {
+    const RAW_ID: u8 = 0x01;
+    const ARGON2ID_ID: u8 = 0x02;
+    const PBKDF2_ID: u8 = 0x03;
+
+    /// Default parameters for new stores using the KDF.
+    fn new(kdf: Kdf) -> Self {
+        match kdf {
+            Kdf::Argon2 => Self::Argon2id(DEFAULT_KDF_PARAMS),
+            Kdf::Pbkdf2 => Self::Pbkdf2 {
+                iterations: PBKDF2_ITERATIONS,
+            },
+            Kdf::None => Self::Raw,
+        }
+    }
+
+    fn kdf(self) -> Kdf {
+        match self {
+            Self::Raw => Kdf::None,
+            Self::Argon2id(_) => Kdf::Argon2,
+            Self::Pbkdf2 { .. } => Kdf::Pbkdf2,
+        }
+    }
+
+    /// Serialize as the KDF id followed by its parameters, zero-padded.
+    fn to_bytes(self) -> [u8; KDF_HEADER_LENGTH] {
+        let mut bytes = [0; KDF_HEADER_LENGTH];
+        match self {
+            Self::Raw => bytes[0] = Self::RAW_ID,
+            Self::Argon2id(params) => {
+                bytes[0] = Self::ARGON2ID_ID;
+                bytes[1..].copy_from_slice(&params.to_bytes());
+            }
+            Self::Pbkdf2 { iterations } => {
+                bytes[0] = Self::PBKDF2_ID;
+                bytes[1..5].copy_from_slice(&iterations.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Read the KDF from a header, rejecting ids and parameters no store would have been written with.
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < KDF_HEADER_LENGTH {
+            return None;
+        }
+        match bytes[0] {
+            Self::RAW_ID => Some(Self::Raw),
+            Self::ARGON2ID_ID => KdfParams::from_bytes(&bytes[1..]).map(Self::Argon2id),
+            Self::PBKDF2_ID => {
+                let mut word = [0; 4];
+                word.copy_from_slice(&bytes[1..5]);
+                let iterations = u32::from_le_bytes(word);
+                let sane = (100_000..=100_000_000).contains(&iterations);
+                Some(Self::Pbkdf2 { iterations }).filter(|_| sane)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Authenticated ciphers the store can be encrypted with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cipher {
+    /// AES-256-GCM, which every store used before the cipher was recorded
+    AesGcm,
+    /// AES-256-GCM-SIV, which stays secure even if a nonce is ever reused
+    AesGcmSiv,
+    /// ChaCha20-Poly1305, which is faster than AES without hardware acceleration
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    const GCM_ID: u8 = 0x01;
+    const GCM_SIV_ID: u8 = 0x02;
+    const CHACHA20_POLY1305_ID: u8 = 0x03;
+
+    /// Names the ciphers are given on the command line.
+    pub fn variants() -> [&'static str; 3] {
+        ["gcm", "gcm-siv", "chacha20-poly1305"]
+    }
+
+    fn id(self) -> u8 {
+        match self {
+            Self::AesGcm => Self::GCM_ID,
+            Self::AesGcmSiv => Self::GCM_SIV_ID,
+            Self::ChaCha20Poly1305 => Self::CHACHA20_POLY1305_ID,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            Self::GCM_ID => Some(Self::AesGcm),
+            Self::GCM_SIV_ID => Some(Self::AesGcmSiv),
+            Self::CHACHA20_POLY1305_ID => Some(Self::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::AesGcm => "gcm",
+            Self::AesGcmSiv => "gcm-siv",
+            Self::ChaCha20Poly1305 => "chacha20-poly1305",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gcm" => Ok(Self::AesGcm),
+            "gcm-siv" => Ok(Self::AesGcmSiv),
+            "chacha20-poly1305" => Ok(Self::ChaCha20Poly1305),
+            _ => Err(format!("valid values: {}", Cipher::variants().join(", "))),
+        }
+    }
+}
+
+/// Return where the store goes when no path is given: `ppa/store.bin` under the
+/// platform's data directory, like `~/.local/share` or `%APPDATA%`.
+pub fn default_store_path() -> Result<PathBuf> {
+    dirs_next::data_dir()
+        .map(|dir| dir.join("ppa").join("store.bin"))
+        .ok_or_else(|| anyhow!("Could not find user's data directory"))
+}
+
+/// Return where the store was kept before it moved to the data directory, `~/.ppa.bin`.
+pub fn legacy_store_path() -> Result<PathBuf> {
+    home::home_dir()
+        .map(|home| home.join(".ppa.bin"))
+        .ok_or_else(|| anyhow!("Could not find user's home directory"))
+}
+
+/// Return a path to the store file, which defaults to `default_store_path`.
+///
+/// While the default store hasn't been created, a store left at the legacy
+/// `~/.ppa.bin` is used in its place.
+///
+/// A leading `~` in the given path is expanded to the home directory, and a
+/// relative path is resolved against the current directory. The directory
+/// the store goes in has to exist already.
+pub fn path_to_store(store: Option<&Path>) -> Result<PathBuf> {
+    let home = || home::home_dir().ok_or_else(|| anyhow!("Could not find user's home directory"));
+    let path = match store {
+        None => {
+            let path = default_store_path()?;
+            let legacy = legacy_store_path()?;
+            if !path.exists() && legacy.exists() {
+                debug!("Using the store at its old location");
+                return Ok(legacy);
+            }
+            return Ok(path);
+        }
+        Some(path) => match path.strip_prefix("~") {
+            Ok(rest) => home()?.join(rest),
+            Err(_) if path.is_relative() => std::env::current_dir()?.join(path),
+            Err(_) => path.to_path_buf(),
+        },
+    };
+    match path.parent() {
+        Some(parent) if !parent.is_dir() => Err(anyhow!(
+            "The directory for the store does not exist: {}",
+            parent.display()
+        )),
+        _ => Ok(path),
+    }
+}
+
+/// Check whether the store file exists on the user's system.
+pub fn store_exists(path: &Path) -> bool {
+    path.exists()
+}
+
+/// Derive a 32-byte encryption key from the user's password.
+fn derive_key(
+    encryption_password: &str,
+    salt: &[u8],
+    derivation: KeyDerivation,
+) -> Result<Zeroizing<Vec<u8>>> {
+    match derivation {
+        KeyDerivation::Raw if encryption_password.len() != 32 => Err(anyhow!(
+            "Stores without a KDF require a password of exactly 32 bytes, but it is {} bytes",
+            encryption_password.len()
+        )),
+        KeyDerivation::Raw => Ok(Zeroizing::new(encryption_password.as_bytes().to_vec())),
+        KeyDerivation::Argon2id(params) => {
+            let config = argon2::Config {
+                variant: argon2::Variant::Argon2id,
+                mem_cost: params.mem_cost,
+                time_cost: params.time_cost,
+                hash_length: 32,
+                ..argon2::Config::default()
+            };
+            argon2::hash_raw(encryption_password.as_bytes(), salt, &config)
+                .map(Zeroizing::new)
+                .map_err(|e| anyhow!("Could not derive encryption key: {}", e))
+        }
+        KeyDerivation::Pbkdf2 { iterations } => {
+            let mut key = Zeroizing::new(vec![0; 32]);
+            pbkdf2::pbkdf2_hmac::<Sha256>(
+                encryption_password.as_bytes(),
+                salt,
+                iterations,
+                &mut key,
+            );
+            Ok(key)
+        }
+    }
+}
+
+/// Whether stores are read-only, as set with `set_read_only`.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Refuse, or allow again, every change to a store on disk.
+///
+/// While stores are read-only, writing, creating, moving, or restoring one
+/// fails with `ReadOnly`, and `load_store` leaves older stores unmigrated.
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+/// Return whether stores are read-only.
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// The error when a store would be changed while stores are read-only.
+#[derive(Debug)]
+pub struct ReadOnly;
+
+impl fmt::Display for ReadOnly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The store is read-only")
+    }
+}
+
+impl std::error::Error for ReadOnly {}
+
+/// Fail with `ReadOnly` if stores are read-only.
+pub(crate) fn ensure_writable() -> Result<()> {
+    if is_read_only() {
+        return Err(ReadOnly.into());
+    }
+    Ok(())
+}
+
+/// The error when a store can't be decrypted with the password it was given.
+#[derive(Debug)]
+pub struct WrongPassword;
+
+impl fmt::Display for WrongPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Wrong password, or the store is corrupted")
+    }
+}
+
+impl std::error::Error for WrongPassword {}
+
+/// The error for a store file too short to hold its header and ciphertext.
+fn truncated() -> anyhow::Error {
+    anyhow!("Store file is truncated; restore it from a backup with `ppa restore-backup`")
+}
+
+/// Decrypt content that is prefixed with its nonce.
+fn decrypt(key: &[u8], content: &[u8], cipher: Cipher) -> Result<Zeroizing<Vec<u8>>> {
+    if content.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(truncated());
+    }
+    let (nonce_raw, content_encrypted) = content.split_at(NONCE_LENGTH);
+    let key = GenericArray::from_slice(key);
+    let nonce: GenericArray<u8, U12> = *GenericArray::from_slice(nonce_raw);
+    match cipher {
+        Cipher::AesGcm => Aes256Gcm::new(key).decrypt(&nonce, content_encrypted),
+        Cipher::AesGcmSiv => Aes256GcmSiv::new(key).decrypt(&nonce, content_encrypted),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key).decrypt(&nonce, content_encrypted),
+    }
+    .map(Zeroizing::new)
+    .map_err(|e| anyhow!("Could not decrypt store: {}", e))
+}
+
+/// Encrypt content with the given nonce, which must never have been used with the key before.
+fn encrypt(
+    key: &[u8],
+    nonce: &GenericArray<u8, U12>,
+    content: &[u8],
+    cipher: Cipher,
+) -> Result<Vec<u8>> {
+    let key = GenericArray::from_slice(key);
+    match cipher {
+        Cipher::AesGcm => Aes256Gcm::new(key).encrypt(nonce, content),
+        Cipher::AesGcmSiv => Aes256GcmSiv::new(key).encrypt(nonce, content),
+        Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key).encrypt(nonce, content),
+    }
+    .map_err(|e| anyhow!("Could not encrypt: {}", e))
+}
+
+/// Decrypt content laid out as salt|nonce|ciphertext, deriving the key the given way.
+fn decrypt_salted(
+    encryption_password: &str,
+    content: &[u8],
+    derivation: KeyDerivation,
+    cipher: Cipher,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let (salt, content) = content.split_at(SALT_LENGTH.min(content.len()));
+    let key = derive_key(encryption_password, salt, derivation)?;
+    decrypt(&key, content, cipher)
+}
+
+/// Decrypt a store written before key derivation was added, where the
+/// password bytes were used directly as the key and the file had no salt.
+fn decrypt_legacy(encryption_password: &str, file_content: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    if encryption_password.len() != 32 {
+        return Err(anyhow!(
+            "Legacy stores require a password of exactly 32 bytes, but it is {} bytes",
+            encryption_password.len()
+        ));
+    }
+    decrypt(encryption_password.as_bytes(), file_content, Cipher::AesGcm)
+}
+
+/// Decrypt a store written before the magic bytes were added, trying each
+/// older layout from newest to oldest.
+///
+/// Fails with `WrongPassword` if the file is long enough for one of the
+/// layouts but doesn't decrypt with the password.
+fn decrypt_headerless(
+    encryption_password: &str,
+    file_content: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    if let Some(params) = KdfParams::from_bytes(file_content) {
+        let content = &file_content[KDF_PARAMS_LENGTH..];
+        if let Ok(d) = decrypt_salted(
+            encryption_password,
+            content,
+            KeyDerivation::Argon2id(params),
+            Cipher::AesGcm,
+        ) {
+            return Ok(d);
+        }
+    }
+    decrypt_salted(
+        encryption_password,
+        file_content,
+        KeyDerivation::Argon2id(DEFAULT_KDF_PARAMS),
+        Cipher::AesGcm,
+    )
+    .or_else(|_| decrypt_legacy(encryption_password, file_content))
+    .map_err(|_| {
+        let salted = file_content.len() >= SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH;
+        if salted || encryption_password.len() == 32 {
+            WrongPassword.into()
+        } else {
+            anyhow!("Not a ppa store, or the password is wrong")
+        }
+    })
+}
+
+/// Load the store into memory, decrypt, and deserialize into structs.
+///
+/// Stores are laid out as magic|version|cipher id|kdf id|kdf params|salt|nonce|ciphertext.
+/// Stores from older versions, which leave out the ids, and files without the
+/// magic bytes are read using the older layouts, and a store read that way is
+/// rewritten in the current layout with the same cipher and KDF, unless stores
+/// are read-only.
+pub fn load_store(path: &Path, encryption_password: &str) -> Result<Vec<Entry>> {
+    let (entries, outdated) = decrypt_store(path, encryption_password)?;
+    if outdated && !is_read_only() {
+        write_store(path, &entries, encryption_password, backup::DEFAULT_BACKUPS)?;
+        info!("Migrated store to the current format");
+    }
+    Ok(entries)
+}
+
+/// Load the store as `load_store` does, but never write to it, leaving a store
+/// in an older layout as it is.
+///
+/// The file is only ever opened for reading, so not even its modification time changes.
+pub fn read_store(path: &Path, encryption_password: &str) -> Result<Vec<Entry>> {
+    decrypt_store(path, encryption_password).map(|(entries, _)| entries)
+}
+
+/// Read and decrypt the store, also returning whether it's in an older layout.
+fn decrypt_store(path: &Path, encryption_password: &str) -> Result<(Vec<Entry>, bool)> {
+    debug!("Reading store");
+    if !path.exists() {
+        debug!("Store file does not exist");
+        return Err(anyhow!("File does not exist: initialize with `ppa init`"));
+    }
+
+    let file_content = fs::read(path)?;
+    // The oldest layout, the smallest possible store, is a nonce and an encrypted empty list
+    if file_content.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(truncated());
+    }
+    let (decrypted, outdated) = match file_content.strip_prefix(MAGIC) {
+        Some(rest) => {
+            let (version, rest) = rest.split_first().ok_or_else(truncated)?;
+            let header_length = match *version {
+                1 => KDF_PARAMS_LENGTH,
+                2 => KDF_HEADER_LENGTH,
+                FORMAT_VERSION => 1 + KDF_HEADER_LENGTH,
+                _ => {
+                    return Err(anyhow!(
+                        "Unsupported store format version {}; upgrade ppa to read it",
+                        version
+                    ))
+                }
+            };
+            if rest.len() < header_length + SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH {
+                return Err(truncated());
+            }
+            let (cipher, derivation) = match *version {
+                1 => Some(Cipher::AesGcm)
+                    .zip(KdfParams::from_bytes(rest).map(KeyDerivation::Argon2id)),
+                2 => Some(Cipher::AesGcm).zip(KeyDerivation::from_bytes(rest)),
+                _ => Cipher::from_id(rest[0]).zip(KeyDerivation::from_bytes(&rest[1..])),
+            }
+            .ok_or_else(|| anyhow!("Store header is corrupted"))?;
+            let decrypted = decrypt_salted(
+                encryption_password,
+                &rest[header_length..],
+                derivation,
+                cipher,
+            )
+            .map_err(|_| WrongPassword)?;
+            (decrypted, *version != FORMAT_VERSION)
+        }
+        None => {
+            debug!("Store has no header, trying older formats");
+            (
+                decrypt_headerless(encryption_password, &file_content)?,
+                true,
+            )
+        }
+    };
+    let decrypted_str = std::str::from_utf8(&decrypted)
+        .map_err(|e| anyhow!("Store decrypted, but it is not valid UTF-8: {}", e))?;
+
+    let entries: Vec<Entry> = serde_json::from_str(decrypted_str)
+        .map_err(|e| anyhow!("Store decrypted, but its entries could not be read: {}", e))?;
+    debug!("Read {} entries from the store", entries.len());
+    Ok((entries, outdated))
+}
+
+/// Return the cipher and KDF the store at the path was written with.
+///
+/// Stores from before either was recorded use AES-256-GCM and Argon2, and
+/// paths with no store yet get the defaults for new stores.
+fn stored_settings(path: &Path) -> (Cipher, Kdf) {
+    let mut header = Vec::new();
+    let read = File::open(path).and_then(|file| {
+        file.take((MAGIC.len() + 2 + KDF_HEADER_LENGTH) as u64)
+            .read_to_end(&mut header)
+    });
+    if read.is_err() {
+        return (DEFAULT_CIPHER, Kdf::Argon2);
+    }
+    let stored_kdf =
+        |bytes| KeyDerivation::from_bytes(bytes).map_or(Kdf::Argon2, KeyDerivation::kdf);
+    match header.strip_prefix(MAGIC) {
+        Some([FORMAT_VERSION, cipher, rest @ ..]) => (
+            Cipher::from_id(*cipher).unwrap_or(Cipher::AesGcm),
+            stored_kdf(rest),
+        ),
+        Some([2, rest @ ..]) => (Cipher::AesGcm, stored_kdf(rest)),
+        _ => (Cipher::AesGcm, Kdf::Argon2),
+    }
+}
+
+/// Create a new, empty store encrypted with the given cipher, whose key is
+/// derived with the given KDF, along with the directory it goes in.
+pub fn create_store(
+    path: &Path,
+    encryption_password: &str,
+    kdf: Kdf,
+    cipher: Cipher,
+) -> Result<()> {
+    ensure_writable()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_derived(
+        path,
+        &[],
+        encryption_password,
+        0,
+        KeyDerivation::new(kdf),
+        cipher,
+    )
+}
+
+/// Move a store, along with its backups, creating the directory it moves into.
+pub fn move_store(from: &Path, to: &Path) -> Result<()> {
+    ensure_writable()?;
+    if to.exists() {
+        return Err(anyhow!("A store already exists at {}", to.display()));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    debug!("Moving store from {} to {}", from.display(), to.display());
+    fs::rename(from, to)?;
+    for backup in backup::list_backups(from)? {
+        fs::rename(&backup.path, backup::backup_path(to, backup.number))?;
+    }
+    Ok(())
+}
+
+/// Serialize the store, encrypt, and write to disk.
+///
+/// The store is written to a temporary file next to it first and then moved
+/// into place, so a failed write never leaves a partial store behind. The
+/// store being replaced is kept as a backup, up to `backups` of them. The
+/// store keeps whichever cipher and KDF it was created with.
+pub fn write_store(
+    path: &Path,
+    entries: &[Entry],
+    encryption_password: &str,
+    backups: usize,
+) -> Result<()> {
+    let (cipher, kdf) = stored_settings(path);
+    write_derived(
+        path,
+        entries,
+        encryption_password,
+        backups,
+        KeyDerivation::new(kdf),
+        cipher,
+    )
+}
+
+/// Write the store as `write_store` does, deriving the key and encrypting the given ways.
+fn write_derived(
+    path: &Path,
+    entries: &[Entry],
+    encryption_password: &str,
+    backups: usize,
+    derivation: KeyDerivation,
+    cipher: Cipher,
+) -> Result<()> {
+    ensure_writable()?;
+    debug!("Writing store");
+    let content = Zeroizing::new(serde_json::to_string(&entries)?);
+
+    let salt: [u8; SALT_LENGTH] = thread_rng().gen();
+    let key = derive_key(encryption_password, &salt, derivation)?;
+    let nonce_raw: [u8; NONCE_LENGTH] = thread_rng().gen();
+    let nonce: GenericArray<u8, U12> = *GenericArray::from_slice(&nonce_raw);
+    let ciphertext = encrypt(&key, &nonce, content.as_bytes(), cipher)?;
+    let to_disk: Vec<u8> = MAGIC
+        .iter()
+        .chain(&[FORMAT_VERSION, cipher.id()])
+        .chain(derivation.to_bytes().iter())
+        .chain(salt.iter())
+        .chain(nonce.iter())
+        .chain(ciphertext.iter())
+        .cloned()
+        .collect();
+
+    backup::rotate_backups(path, backups)?;
+    write_atomically(path, &to_disk)
+}
+
+/// Replace the file's contents without ever leaving it partially written.
+///
+/// The content is written and synced to a temporary file next to the target,
+/// which is then renamed over it. `fs::rename` replaces an existing file on
+/// Windows as well, so this is atomic on every platform ppa supports.
+fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    let mut temp_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Store path is not a file: {}", path.display()))?
+        .to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    let written = create_private_file(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Create (or truncate) a file that only the current user can read and write.
+#[cfg(unix)]
+pub fn create_private_file(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // The mode only applies to new files, so tighten a leftover one as well
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// Create (or truncate) a file, leaving access to the default ACL on Windows.
+#[cfg(not(unix))]
+pub fn create_private_file(path: &Path) -> std::io::Result<File> {
+    File::create(path)
+}
+
+/// Return the store's permission bits if anyone other than its owner can access it.
+#[cfg(unix)]
+pub fn loose_permissions(path: &Path) -> Result<Option<u32>> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path)?.permissions().mode() & 0o777;
+    Ok(Some(mode).filter(|mode| mode & 0o077 != 0))
+}
+
+/// Permissions aren't checked on Windows, where the store uses the default ACL.
+#[cfg(not(unix))]
+pub fn loose_permissions(_path: &Path) -> Result<Option<u32>> {
+    Ok(None)
+}
+
+/// Make the store readable and writable only by its owner.
+#[cfg(unix)]
+pub fn restrict_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    ensure_writable()?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(())
+}
+
+/// Permissions aren't changed on Windows, where the store uses the default ACL.
+#[cfg(not(unix))]
+pub fn restrict_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Sync the directory holding the file so that a rename into it survives a crash.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    if let Err(e) = File::open(parent).and_then(|dir| dir.sync_all()) {
+        debug!("Could not sync store directory: {}", e);
+    }
+}
+
+/// Directories can't be opened for syncing on Windows; the rename is already durable there.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) {}
@@ -0,0 +1,22 @@
+use zxcvbn::zxcvbn;
+
+/// Lowest strength score that isn't considered weak.
+pub const MIN_RECOMMENDED_STRENGTH: u8 = 2;
+
+/// Estimate how hard a password is to guess, from 0 (trivial) to 4 (very hard).
+///
+/// `user_inputs` are words like the entry's name and username, which make a
+/// password weaker if it contains them.
+pub fn password_strength(password: &str, user_inputs: &[&str]) -> u8 {
+    zxcvbn(password, user_inputs).score().into()
+}
+
+/// Describe a strength score from `password_strength` in a word or two.
+pub fn strength_label(score: u8) -> &'static str {
+    match score {
+        0 | 1 => "weak",
+        2 => "fair",
+        3 => "strong",
+        _ => "very strong",
+    }
+}
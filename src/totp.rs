@@ -0,0 +1,30 @@
+use anyhow::{anyhow, Result};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+/// Normalize a base32 TOTP secret the way authenticator apps display them,
+/// dropping spaces and padding and uppercasing it.
+///
+/// Returns an error if what's left isn't valid base32.
+pub fn normalize_totp_secret(secret: &str) -> Result<String> {
+    let normalized: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=')
+        .collect::<String>()
+        .to_uppercase();
+    match Secret::Encoded(normalized.clone()).to_bytes() {
+        Ok(bytes) if !bytes.is_empty() => Ok(normalized),
+        _ => Err(anyhow!("TOTP secret is not valid base32")),
+    }
+}
+
+/// Compute the current 6-digit code for a base32 secret, along with the
+/// number of seconds until it changes.
+pub fn totp_code(secret: &str) -> Result<(String, u64)> {
+    let bytes = Secret::Encoded(normalize_totp_secret(secret)?)
+        .to_bytes()
+        .map_err(|e| anyhow!("Could not decode TOTP secret: {}", e))?;
+    let totp = TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, bytes);
+    let code = totp.generate_current()?;
+    let remaining = totp.ttl()?;
+    Ok((code, remaining))
+}
@@ -0,0 +1,173 @@
+use crate::{
+    prompt::{prompt_confirm, prompt_new_store_password, prompt_password, read_stdin_line},
+    PasswordOptions,
+};
+use log::{debug, error, info, warn};
+use ppa::Entry;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+use zeroize::Zeroizing;
+
+/// Number of times a typed store password is tried before giving up.
+const PASSWORD_ATTEMPTS: usize = 3;
+
+/// Exit code when the store password was wrong, after every attempt if it was typed in.
+const WRONG_PASSWORD_EXIT_CODE: i32 = 2;
+
+/// Read the store password from a file, without the line ending after it.
+fn read_password_file(path: &Path) -> String {
+    debug!("Reading the store password from {}", path.display());
+    match fs::read_to_string(path) {
+        Ok(content) => content.trim_end_matches(&['\r', '\n'][..]).to_owned(),
+        Err(e) => {
+            error!("Could not read password file {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Get the store password from the password file, stdin, or the `PPA_PASSWORD`
+/// environment variable, if one of them was asked for or set.
+pub(crate) fn supplied_password(options: &PasswordOptions) -> Option<String> {
+    if let Some(path) = &options.password_file {
+        return Some(read_password_file(path));
+    }
+    if options.password_stdin {
+        if atty::is(atty::Stream::Stdin) {
+            error!("--password-stdin needs the password piped in, but stdin is a terminal");
+            process::exit(1);
+        }
+        debug!("Reading the store password from stdin");
+        return Some(read_stdin_line());
+    }
+    env::var("PPA_PASSWORD").ok().inspect(|_| {
+        warn!("Using the store password from PPA_PASSWORD");
+    })
+}
+
+/// Get the store password from wherever `supplied_password` finds it, or prompt for it.
+///
+/// A new password is confirmed and must meet the minimum length. The password
+/// is wiped from memory when it's dropped.
+pub(crate) fn store_password(new: bool, options: &PasswordOptions) -> Zeroizing<String> {
+    let password = match supplied_password(options) {
+        Some(p) => {
+            if new {
+                if let Err(e) = ppa::check_password_length(&p) {
+                    error!("{}", e);
+                    process::exit(1);
+                }
+            }
+            p
+        }
+        None if new => prompt_new_store_password("Store password"),
+        None => prompt_password("Store password", false),
+    };
+    Zeroizing::new(password)
+}
+
+/// Get the store password and load the store, asking again if a typed password is wrong.
+///
+/// A wrong password exits with `WRONG_PASSWORD_EXIT_CODE` once `PASSWORD_ATTEMPTS`
+/// have been used up. Passwords that weren't typed in are only tried once, and
+/// so is everything when `retry` is false.
+pub(crate) fn unlock_store(
+    store_path: &Path,
+    options: &PasswordOptions,
+    retry: bool,
+) -> (Zeroizing<String>, Vec<Entry>) {
+    let retry = retry && options.typed();
+    let mut attempt = 1;
+    loop {
+        let encryption_password = store_password(false, options);
+        match ppa::load_store(store_path, &encryption_password) {
+            Ok(entries) => return (encryption_password, entries),
+            Err(e) if retry && attempt < PASSWORD_ATTEMPTS && e.is::<ppa::WrongPassword>() => {
+                warn!("{}; try again", e);
+                attempt += 1;
+            }
+            Err(e) if e.is::<ppa::WrongPassword>() => {
+                error!("Could not load store: {}", e);
+                process::exit(WRONG_PASSWORD_EXIT_CODE);
+            }
+            Err(e) => {
+                error!("Could not load store: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Load a store other than the one in use, prompting for its own password.
+pub(crate) fn open_other_store(path: &Path) -> Vec<Entry> {
+    let path = match ppa::path_to_store(Some(path)) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not determine store path: {}", e);
+            process::exit(1);
+        }
+    };
+    let password = Zeroizing::new(prompt_password(
+        &format!("Store password for {}", path.display()),
+        false,
+    ));
+    match ppa::read_store(&path, &password) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Could not load {}: {}", path.display(), e);
+            if e.is::<ppa::WrongPassword>() {
+                process::exit(WRONG_PASSWORD_EXIT_CODE);
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// Warn if other users can access the store, and offer to fix it unless it's read-only.
+pub(crate) fn check_permissions(store_path: &Path, read_only: bool) {
+    let mode = match ppa::loose_permissions(store_path) {
+        Ok(Some(mode)) => mode,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Could not check the store's permissions: {}", e);
+            return;
+        }
+    };
+    warn!("The store can be accessed by other users (mode {:o})", mode);
+    if read_only || !atty::is(atty::Stream::Stdin) || !prompt_confirm("Restrict it to your user?") {
+        return;
+    }
+    match ppa::restrict_permissions(store_path) {
+        Ok(()) => info!("Store permissions set to 600"),
+        Err(e) => warn!("Could not change the store's permissions: {}", e),
+    }
+}
+
+/// Offer to move a store still at the legacy `~/.ppa.bin` to the default
+/// location, returning wherever the store is afterwards.
+pub(crate) fn offer_store_move(store_path: PathBuf) -> PathBuf {
+    let (legacy, default) = match (ppa::legacy_store_path(), ppa::default_store_path()) {
+        (Ok(legacy), Ok(default)) => (legacy, default),
+        _ => return store_path,
+    };
+    if store_path != legacy || !atty::is(atty::Stream::Stdin) {
+        return store_path;
+    }
+    info!("The store is at its old location, {}", legacy.display());
+    if !prompt_confirm(&format!("Move it to {}?", default.display())) {
+        return store_path;
+    }
+    match ppa::move_store(&legacy, &default) {
+        Ok(()) => {
+            info!("Store moved to {}", default.display());
+            default
+        }
+        Err(e) => {
+            warn!("Could not move the store: {}", e);
+            store_path
+        }
+    }
+}